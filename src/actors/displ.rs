@@ -1,8 +1,12 @@
-use std::fmt::Write;
+use std::{collections::HashMap, fmt::Write, sync::{Arc, Mutex}, time::{Duration, Instant}};
 
-use indicatif::{ProgressStyle, ProgressBar, ProgressState};
+use clap::ValueEnum;
+use indicatif::{MultiProgress, ProgressStyle, ProgressBar, ProgressState, ProgressDrawTarget};
+use serde_json::json;
 use tiny_tokio_actor::{Actor, Message, Handler, async_trait, ActorContext};
 
+use crate::clock::{Clock, SystemClock};
+
 use super::event::Event;
 
 /// --------------------------- ///
@@ -13,7 +17,88 @@ use super::event::Event;
 #[derive(Clone)]
 pub struct Displ {
     pub spinner_style: ProgressStyle,
-    pub progress_bar: Option<ProgressBar>,
+    /// The progress bar of each target being cleaned, by the name of the target, so that concurrent targets do not
+    /// share a bar.
+    pub progress_bars: HashMap<String, ProgressBar>,
+    /// The indeterminate spinner displayed during the long steps, if any.
+    pub spinner: Option<ProgressBar>,
+    /// The outer progress bar of the projects cleaned, when several projects are cleaned.
+    pub projects_bar: Option<ProgressBar>,
+    /// The bars displayed together, under the projects progress bar if any.
+    pub multi: MultiProgress,
+    /// Whether the output uses ANSI colors.
+    pub colors: bool,
+    /// The format of the output.
+    pub output: OutputFormat,
+    /// Whether the per item messages are hidden, only keeping the progress and the final report.
+    pub summary_only: bool,
+    /// The maximum number of redraws per second of the progress bar and the spinner.
+    pub refresh_hz: u8,
+    /// Whether the messages are printed on stderr, leaving stdout to the ids of the erased items.
+    pub to_stderr: bool,
+    /// The messages held back until flushed, when buffered. The progress bars are then hidden.
+    pub buffer: Option<Vec<String>>,
+    /// The warnings displayed since the last report of the warnings.
+    pub warnings: Vec<String>,
+    /// The clock the countdowns are timed with.
+    pub clock: Arc<dyn Clock>,
+    /// The lines printed, when captured instead of being printed.
+    pub captured: Option<Arc<Mutex<Vec<String>>>>,
+}
+
+/// Configuration used to create the display actor.
+#[derive(Clone, Debug)]
+pub struct DisplConfig {
+    /// Whether the output uses ANSI colors.
+    pub colors: bool,
+    /// The format of the output.
+    pub output: OutputFormat,
+    /// Whether the per item messages are hidden, only keeping the progress and the final report.
+    pub summary_only: bool,
+    /// The maximum number of redraws per second of the progress bar and the spinner, clamped between 1 and 60.
+    pub refresh_hz: u8,
+    /// Whether the messages are printed on stderr, leaving stdout to the ids of the erased items.
+    pub to_stderr: bool,
+    /// Whether the messages are held back until flushed, the progress bars being hidden.
+    pub buffered: bool,
+    /// The clock the countdowns are timed with.
+    pub clock: Arc<dyn Clock>,
+    /// Where the printed lines are captured instead of being printed, e.g. to check the output of a run.
+    pub captured: Option<Arc<Mutex<Vec<String>>>>,
+}
+
+impl Default for DisplConfig {
+    fn default() -> Self {
+        DisplConfig {
+            colors: true,
+            output: OutputFormat::Text,
+            summary_only: false,
+            refresh_hz: DEFAULT_REFRESH_HZ,
+            to_stderr: false,
+            buffered: false,
+            clock: Arc::new(SystemClock),
+            captured: None,
+        }
+    }
+}
+
+/// Enum used to define the format of the output.
+#[derive(Clone, Debug, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable messages and progress bars.
+    Text,
+    /// One JSON object per line, emitted as each job is processed.
+    Jsonl,
+    /// The events of the bus (project resolved, page fetched, item erased/skipped/failed), one JSON object per line,
+    /// written by a subscriber of the bus.
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// Whether the output is made of JSON objects rather than human readable messages and progress bars.
+    pub fn is_json(&self) -> bool {
+        *self != OutputFormat::Text
+    }
 }
 
 /// Display actor implementation.
@@ -22,11 +107,96 @@ impl Actor<Event> for Displ {}
 /// Default implementation for the display actor.
 impl Default for Displ {
     fn default() -> Self {
-        Displ {
-            spinner_style :ProgressStyle::with_template("{prefix:.bold.dim} {spinner} {wide_msg}").unwrap().tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
-            progress_bar: None,
+        Displ::new(DisplConfig::default())
+    }
+}
+
+/// The default redraw rate of the progress bar, the one of `indicatif`.
+pub const DEFAULT_REFRESH_HZ: u8 = 20;
+
+impl Displ {
+    /// Creates a new display actor from the given configuration.
+    pub fn new(config: DisplConfig) -> Self {
+        let displ = Displ {
+            spinner_style :ProgressStyle::with_template(spinner_template(config.colors)).unwrap().tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
+            progress_bars: HashMap::new(),
+            spinner: None,
+            projects_bar: None,
+            multi: MultiProgress::new(),
+            colors: config.colors,
+            output: config.output,
+            summary_only: config.summary_only,
+            refresh_hz: config.refresh_hz.clamp(1, 60),
+            to_stderr: config.to_stderr,
+            buffer: config.buffered.then(Vec::new),
+            warnings: Vec::new(),
+            clock: config.clock,
+            captured: config.captured,
+        };
+        displ.multi.set_draw_target(displ.draw_target());
+        displ
+    }
+
+    /// Prints a line on stdout, or on stderr when the messages are printed on stderr, unless the lines are captured.
+    fn print_line(&self, line: &str) {
+        if let Some(captured) = &self.captured {
+            captured.lock().unwrap().push(line.to_string());
+        } else if self.to_stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
         }
     }
+
+    /// Displays the bar with the other bars, under the projects progress bar if any.
+    fn add_bar(&self, bar: ProgressBar) -> ProgressBar {
+        self.multi.add(bar)
+    }
+
+    /// Displays a line already formatted for the output, or holds it back when buffered.
+    fn display_line(&mut self, line: String) {
+        if let Some(buffer) = &mut self.buffer {
+            buffer.push(line);
+            return;
+        }
+        // The progress bars are hidden while printing, then redrawn at their current position.
+        self.multi.suspend(|| self.print_line(&line));
+    }
+
+    /// The draw target of the progress bar and the spinner, redrawn at most at the configured rate.
+    fn draw_target(&self) -> ProgressDrawTarget {
+        if self.buffer.is_some() {
+            return ProgressDrawTarget::hidden();
+        }
+        ProgressDrawTarget::stderr_with_hz(self.refresh_hz)
+    }
+}
+
+/// The template of the spinner, with or without colors.
+pub fn spinner_template(colors: bool) -> &'static str {
+    if colors {
+        "{prefix:.bold.dim} {spinner} {wide_msg}"
+    } else {
+        "{prefix} {spinner} {wide_msg}"
+    }
+}
+
+/// The template of the progress bar, with or without colors.
+pub fn progress_template(colors: bool) -> &'static str {
+    if colors {
+        "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta})"
+    } else {
+        "{spinner} [{elapsed_precise}] [{wide_bar}] {pos}/{len} ({eta})"
+    }
+}
+
+/// The template of the outer progress bar of the projects, with or without colors.
+pub fn projects_template(colors: bool) -> &'static str {
+    if colors {
+        "projects [{bar:30.magenta/blue}] {pos}/{len}"
+    } else {
+        "projects [{bar:30}] {pos}/{len}"
+    }
 }
 
 /// --------------------------- ///
@@ -50,18 +220,100 @@ impl Message for DisplayMessage {
 #[async_trait]
 impl Handler<Event, DisplayMessage> for Displ {
     async fn handle(&mut self, msg: DisplayMessage, _: &mut ActorContext<Event>) -> Result<(), std::io::Error> {
-        if let Some(pb) = &self.progress_bar {
-            pb.finish_and_clear();
-            self.progress_bar = None;
-        }
-        println!("{}", msg.message);
+        let line = match self.output {
+            OutputFormat::Text => msg.message,
+            OutputFormat::Jsonl | OutputFormat::Ndjson => json!({ "event": "message", "message": msg.message }).to_string(),
+        };
+        self.display_line(line);
         Ok(())
     }
 }
 
-/// Message that allows to initialize the progress bar. 
+/// Message that allows to ask a question to the user, printed at once even when the messages are held back, so that
+/// the question is seen while the answer is awaited.
+#[derive(Clone)]
+pub struct DisplayPrompt {
+    pub question: String
+}
+
+/// Message implementation for the DisplayPrompt message.
+impl Message for DisplayPrompt {
+    /// The type of the result.
+    type Response = ();
+}
+
+/// Handler for the DisplayPrompt message.
+#[async_trait]
+impl Handler<Event, DisplayPrompt> for Displ {
+    async fn handle(&mut self, msg: DisplayPrompt, _: &mut ActorContext<Event>) -> () {
+        let line = match self.output {
+            OutputFormat::Text => msg.question,
+            OutputFormat::Jsonl | OutputFormat::Ndjson => json!({ "event": "prompt", "message": msg.question }).to_string(),
+        };
+        self.multi.suspend(|| self.print_line(&line));
+    }
+}
+
+/// Message that allows to display a non-fatal issue, also collected to be reported with the other warnings.
+#[derive(Clone)]
+pub struct DisplayWarning {
+    pub message: String
+}
+
+/// Message implementation for the DisplayWarning message.
+impl Message for DisplayWarning {
+    /// The type of the result.
+    type Response = ();
+}
+
+/// Handler for the DisplayWarning message.
+#[async_trait]
+impl Handler<Event, DisplayWarning> for Displ {
+    async fn handle(&mut self, msg: DisplayWarning, _: &mut ActorContext<Event>) -> () {
+        let line = match self.output {
+            OutputFormat::Text => format!("Warning: {}", msg.message),
+            OutputFormat::Jsonl | OutputFormat::Ndjson => json!({ "event": "warning", "message": msg.message }).to_string(),
+        };
+        self.warnings.push(msg.message);
+        self.display_line(line);
+    }
+}
+
+/// Message that allows to report the warnings displayed since the last report together, apart from the errors.
+#[derive(Clone)]
+pub struct ReportWarnings;
+
+/// Message implementation for the ReportWarnings message.
+impl Message for ReportWarnings {
+    /// The type of the result.
+    type Response = ();
+}
+
+/// Handler for the ReportWarnings message.
+#[async_trait]
+impl Handler<Event, ReportWarnings> for Displ {
+    async fn handle(&mut self, _: ReportWarnings, _: &mut ActorContext<Event>) -> () {
+        let warnings = std::mem::take(&mut self.warnings);
+        if warnings.is_empty() {
+            return;
+        }
+        if self.output.is_json() {
+            self.display_line(json!({ "event": "warnings", "messages": warnings }).to_string());
+            return;
+        }
+        self.display_line(format!("Warnings ({}):", warnings.len()));
+        for warning in warnings {
+            self.display_line(format!("  - {}", warning));
+        }
+    }
+}
+
+
+/// Message that allows to initialize the progress bar of a target.
 #[derive(Clone)]
 pub struct InitProgressBar {
+    /// The name of the target the bar shows the progress of, e.g. `jobs`.
+    pub bar: String,
     pub message: String,
     pub length: u64,
 }
@@ -76,24 +328,33 @@ impl Message for InitProgressBar {
 #[async_trait]
 impl Handler<Event, InitProgressBar> for Displ {
     async fn handle(&mut self, msg: InitProgressBar, _: &mut ActorContext<Event>) -> () {
-        if let Some(pb) = &self.progress_bar {
+        if self.output.is_json() {
+            return;
+        }
+        if let Some(pb) = self.progress_bars.remove(&msg.bar) {
             pb.finish_and_clear();
         }
-        let new_progress = ProgressBar::new(msg.length);
-        new_progress.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta})")
+        let new_progress = self.add_bar(ProgressBar::with_draw_target(Some(msg.length), self.draw_target()));
+        new_progress.set_style(ProgressStyle::with_template(progress_template(self.colors))
             .unwrap()
             .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
             .progress_chars("#>-"));
 
-        self.progress_bar = Some(new_progress);
-        self.progress_bar.as_mut().unwrap().set_message(msg.message);
+        new_progress.set_message(msg.message);
+        self.progress_bars.insert(msg.bar, new_progress);
     }
 }
 
-/// Message that allows to update the progress bar.
+/// Message that allows to update the progress bar of a target.
 #[derive(Clone)]
 pub struct IncreaseProgress {
+    /// The name of the target the item belongs to, e.g. `jobs`.
+    pub bar: &'static str,
     pub message: String,
+    /// The id of the item that was processed.
+    pub id: u64,
+    /// The event reported for the item in the JSON lines output, e.g. `erased`.
+    pub event: &'static str,
 }
 
 /// Message implementation for the IncreaseProgress message.
@@ -107,10 +368,341 @@ impl Message for IncreaseProgress {
 #[async_trait]
 impl Handler<Event, IncreaseProgress> for Displ {
     async fn handle(&mut self, msg: IncreaseProgress, _: &mut ActorContext<Event>) -> () {
-        if let Some(pb) = &self.progress_bar {
-            pb.set_message(msg.message);
+        // With the events output, the processed items are reported by the subscriber of the bus.
+        if self.output.is_json() {
+            if !self.summary_only && self.output == OutputFormat::Jsonl {
+                let line = json!({ "event": msg.event, "id": msg.id }).to_string();
+                match &mut self.buffer {
+                    Some(buffer) => buffer.push(line),
+                    None => self.print_line(&line),
+                }
+            }
+            return;
+        }
+        if let Some(pb) = self.progress_bars.get(msg.bar) {
+            if !self.summary_only {
+                pb.set_message(msg.message);
+            }
             pb.inc(1);
+            if pb.length().is_some_and(|length| pb.position() >= length) {
+                pb.finish();
+            }
+        }
+    }
+}
+
+/// Message that allows to initialize the outer progress bar of the projects, above the other bars.
+#[derive(Clone)]
+pub struct InitProjectsProgress {
+    /// The number of projects to clean.
+    pub length: u64,
+}
+
+/// Message implementation for the InitProjectsProgress message.
+impl Message for InitProjectsProgress {
+    /// The type of the result.
+    type Response = ();
+}
+
+/// Handler for the InitProjectsProgress message.
+#[async_trait]
+impl Handler<Event, InitProjectsProgress> for Displ {
+    async fn handle(&mut self, msg: InitProjectsProgress, _: &mut ActorContext<Event>) -> () {
+        if self.output.is_json() {
+            return;
         }
+        let projects_bar = self.multi.insert(0, ProgressBar::new(msg.length));
+        projects_bar.set_style(ProgressStyle::with_template(projects_template(self.colors)).unwrap().progress_chars("#>-"));
+        self.projects_bar = Some(projects_bar);
     }
 }
 
+/// Message that allows to advance the outer progress bar of the projects once a project is cleaned.
+#[derive(Clone)]
+pub struct IncreaseProjectsProgress;
+
+/// Message implementation for the IncreaseProjectsProgress message.
+impl Message for IncreaseProjectsProgress {
+    /// The type of the result.
+    type Response = ();
+}
+
+/// Handler for the IncreaseProjectsProgress message.
+#[async_trait]
+impl Handler<Event, IncreaseProjectsProgress> for Displ {
+    async fn handle(&mut self, _: IncreaseProjectsProgress, _: &mut ActorContext<Event>) -> () {
+        if let Some(projects_bar) = &self.projects_bar {
+            projects_bar.inc(1);
+            if projects_bar.length().is_some_and(|length| projects_bar.position() >= length) {
+                projects_bar.finish();
+            }
+        }
+    }
+}
+
+/// Message that allows to finish the progress bar of a target, leaving it at its current position.
+#[derive(Clone)]
+pub struct FinishProgress {
+    /// The name of the target of the bar, e.g. `jobs`.
+    pub bar: String,
+}
+
+/// Message implementation for the FinishProgress message.
+impl Message for FinishProgress {
+    /// The type of the result.
+    type Response = ();
+}
+
+/// Handler for the FinishProgress message.
+#[async_trait]
+impl Handler<Event, FinishProgress> for Displ {
+    async fn handle(&mut self, msg: FinishProgress, _: &mut ActorContext<Event>) -> () {
+        if let Some(pb) = self.progress_bars.get(&msg.bar) {
+            pb.abandon();
+        }
+    }
+}
+
+/// Message that allows to print the buffered messages, or to discard them.
+#[derive(Clone)]
+pub struct FlushMessages {
+    /// Whether the buffered messages are printed rather than discarded.
+    pub print: bool,
+}
+
+/// Message implementation for the FlushMessages message.
+impl Message for FlushMessages {
+    /// The type of the result.
+    /// The messages that were held back.
+    type Response = Vec<String>;
+}
+
+/// Handler for the FlushMessages message.
+#[async_trait]
+impl Handler<Event, FlushMessages> for Displ {
+    async fn handle(&mut self, msg: FlushMessages, _: &mut ActorContext<Event>) -> Vec<String> {
+        let lines = self.buffer.as_mut().map(std::mem::take).unwrap_or_default();
+        if msg.print {
+            for line in &lines {
+                self.print_line(line);
+            }
+        }
+        lines
+    }
+}
+
+/// Message that allows to start an indeterminate spinner during a long step.
+#[derive(Clone)]
+pub struct StartSpinner {
+    pub message: String,
+}
+
+/// Message implementation for the StartSpinner message.
+impl Message for StartSpinner {
+    /// The type of the result.
+    type Response = ();
+}
+
+/// Handler for the StartSpinner message.
+#[async_trait]
+impl Handler<Event, StartSpinner> for Displ {
+    async fn handle(&mut self, msg: StartSpinner, _: &mut ActorContext<Event>) -> () {
+        if self.output.is_json() {
+            return;
+        }
+        if let Some(spinner) = &self.spinner {
+            spinner.finish_and_clear();
+        }
+        let spinner = ProgressBar::new_spinner();
+        spinner.set_draw_target(self.draw_target());
+        let spinner = self.add_bar(spinner);
+        spinner.set_style(self.spinner_style.clone());
+        spinner.set_message(msg.message);
+        spinner.enable_steady_tick(Duration::from_millis(100));
+        self.spinner = Some(spinner);
+    }
+}
+
+/// Message that allows to wait until an instant, displaying a countdown meanwhile.
+#[derive(Clone)]
+pub struct Countdown {
+    pub message: String,
+    /// The instant the countdown ends at, from the `instant` of the clock.
+    pub until: Instant,
+}
+
+/// Message implementation for the Countdown message.
+impl Message for Countdown {
+    /// The type of the result.
+    type Response = ();
+}
+
+/// Handler for the Countdown message. The display is busy until the end of the countdown, and a countdown
+/// already over when handled ends at once, so that the callers waiting for the same date only wait once.
+#[async_trait]
+impl Handler<Event, Countdown> for Displ {
+    async fn handle(&mut self, msg: Countdown, _: &mut ActorContext<Event>) -> () {
+        let Some(remaining) = msg.until.checked_duration_since(self.clock.instant()).filter(|remaining| !remaining.is_zero()) else {
+            return;
+        };
+        if self.output.is_json() || self.buffer.is_some() {
+            let line = format!("{} ({}s)", msg.message, remaining.as_secs());
+            match &mut self.buffer {
+                Some(buffer) => buffer.push(line),
+                None => self.print_line(&json!({ "event": "message", "message": line }).to_string()),
+            }
+            self.clock.sleep(remaining).await;
+            return;
+        }
+        let countdown = ProgressBar::new_spinner();
+        countdown.set_draw_target(self.draw_target());
+        let countdown = self.add_bar(countdown);
+        countdown.set_style(self.spinner_style.clone());
+        countdown.enable_steady_tick(Duration::from_millis(100));
+        while let Some(remaining) = msg.until.checked_duration_since(self.clock.instant()).filter(|remaining| !remaining.is_zero()) {
+            countdown.set_message(format!("{} ({}s left)", msg.message, remaining.as_secs() + 1));
+            self.clock.sleep(remaining.min(Duration::from_secs(1))).await;
+        }
+        countdown.finish_and_clear();
+    }
+}
+
+/// Message that allows to stop the spinner started with StartSpinner.
+#[derive(Clone)]
+pub struct StopSpinner;
+
+/// Message implementation for the StopSpinner message.
+impl Message for StopSpinner {
+    /// The type of the result.
+    type Response = ();
+}
+
+/// Handler for the StopSpinner message.
+#[async_trait]
+impl Handler<Event, StopSpinner> for Displ {
+    async fn handle(&mut self, _: StopSpinner, _: &mut ActorContext<Event>) -> () {
+        if let Some(spinner) = self.spinner.take() {
+            spinner.finish_and_clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tiny_tokio_actor::{ActorPath, ActorSystem, EventBus};
+
+    use super::*;
+    use crate::clock::{FixedClock, MockClock};
+
+    /// The context of the actor when its handlers are called directly.
+    fn context() -> ActorContext<Event> {
+        ActorContext { path: ActorPath::from("/user/displ-actor"), system: ActorSystem::new("test", EventBus::new(100)) }
+    }
+
+    fn date(rfc3339: &str) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&chrono::Utc)
+    }
+
+    #[tokio::test]
+    async fn countdown_ends_with_a_fixed_clock() {
+        let mut displ = Displ::new(DisplConfig { clock: Arc::new(FixedClock(date("2020-01-01T00:00:00Z"))), ..DisplConfig::default() });
+        let countdown = Countdown { message: "Pausing".to_string(), until: Instant::now() + Duration::from_millis(300) };
+        assert!(tokio::time::timeout(Duration::from_secs(5), displ.handle(countdown, &mut context())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn countdown_waits_on_the_clock() {
+        let clock = Arc::new(MockClock::new(date("2024-01-01T00:00:00Z")));
+        let mut displ = Displ::new(DisplConfig { clock: clock.clone(), buffered: true, ..DisplConfig::default() });
+        let until = clock.instant() + Duration::from_millis(2500);
+        displ.handle(Countdown { message: "Pausing".to_string(), until }, &mut context()).await;
+        assert_eq!(*clock.sleeps.lock().unwrap(), vec![Duration::from_millis(2500)]);
+        assert_eq!(displ.buffer, Some(vec!["Pausing (2s)".to_string()]));
+
+        // A countdown already over ends at once.
+        displ.handle(Countdown { message: "Pausing".to_string(), until }, &mut context()).await;
+        assert_eq!(clock.sleeps.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn templates_are_only_styled_with_colors() {
+        for template in [spinner_template(false), progress_template(false), projects_template(false)] {
+            assert!(!template.contains(":."), "{}", template);
+        }
+        assert!(spinner_template(true).contains("{prefix:.bold.dim}"));
+        assert!(progress_template(true).contains("{wide_bar:.cyan/blue}"));
+        assert!(projects_template(true).contains("{bar:30.magenta/blue}"));
+        assert!(!Displ::new(DisplConfig { colors: false, ..DisplConfig::default() }).colors);
+    }
+
+    #[tokio::test]
+    async fn messages_are_wrapped_in_json_objects_with_a_json_output() {
+        let mut displ = Displ::new(DisplConfig { output: OutputFormat::Jsonl, buffered: true, ..DisplConfig::default() });
+        displ.handle(DisplayMessage { message: "Nothing to clean.".to_string() }, &mut context()).await.unwrap();
+        let lines = displ.handle(FlushMessages { print: false }, &mut context()).await;
+        assert_eq!(lines, vec![r#"{"event":"message","message":"Nothing to clean."}"#]);
+    }
+
+    #[tokio::test]
+    async fn summary_only_hides_the_processed_items() {
+        let erased = || IncreaseProgress { bar: "jobs", message: "Job 5 erased.".to_string(), id: 5, event: "erased" };
+        let mut displ = Displ::new(DisplConfig { output: OutputFormat::Jsonl, buffered: true, ..DisplConfig::default() });
+        displ.handle(erased(), &mut context()).await;
+        assert_eq!(displ.handle(FlushMessages { print: false }, &mut context()).await, vec![r#"{"event":"erased","id":5}"#]);
+
+        let mut displ = Displ::new(DisplConfig { output: OutputFormat::Jsonl, buffered: true, summary_only: true, ..DisplConfig::default() });
+        displ.handle(erased(), &mut context()).await;
+        displ.handle(DisplayMessage { message: "Done.".to_string() }, &mut context()).await.unwrap();
+        assert_eq!(displ.handle(FlushMessages { print: false }, &mut context()).await, vec![r#"{"event":"message","message":"Done."}"#]);
+    }
+
+    #[tokio::test]
+    async fn spinner_is_started_and_stopped() {
+        let mut displ = Displ::new(DisplConfig { buffered: true, ..DisplConfig::default() });
+        displ.handle(StartSpinner { message: "Resolving the project cleaner...".to_string() }, &mut context()).await;
+        let spinner = displ.spinner.clone().unwrap();
+        assert_eq!(spinner.message(), "Resolving the project cleaner...");
+        assert!(!spinner.is_finished());
+
+        displ.handle(StopSpinner, &mut context()).await;
+        assert!(displ.spinner.is_none());
+        assert!(spinner.is_finished());
+    }
+
+    #[tokio::test]
+    async fn progress_bar_survives_a_message() {
+        let mut displ = Displ::new(DisplConfig { to_stderr: true, ..DisplConfig::default() });
+        displ.handle(InitProgressBar { bar: "jobs".to_string(), message: "Erasing jobs".to_string(), length: 3 }, &mut context()).await;
+        let increase = || IncreaseProgress { bar: "jobs", message: "Erased".to_string(), id: 1, event: "erased" };
+        displ.handle(increase(), &mut context()).await;
+        displ.handle(DisplayMessage { message: "Pausing the batches".to_string() }, &mut context()).await.unwrap();
+        displ.handle(increase(), &mut context()).await;
+        let bar = &displ.progress_bars["jobs"];
+        assert_eq!((bar.position(), bar.length()), (2, Some(3)));
+        assert!(!bar.is_finished());
+    }
+
+    #[test]
+    fn refresh_rate_is_clamped() {
+        for (configured, applied) in [(0, 1), (15, 15), (200, 60)] {
+            assert_eq!(Displ::new(DisplConfig { refresh_hz: configured, ..DisplConfig::default() }).refresh_hz, applied);
+        }
+    }
+
+    #[tokio::test]
+    async fn projects_bar_advances_per_project() {
+        let mut displ = Displ::new(DisplConfig { buffered: true, ..DisplConfig::default() });
+        displ.handle(InitProjectsProgress { length: 3 }, &mut context()).await;
+        displ.handle(InitProgressBar { bar: "jobs".to_string(), message: "Erasing jobs".to_string(), length: 10 }, &mut context()).await;
+        let projects_bar = displ.projects_bar.clone().unwrap();
+        assert_eq!((projects_bar.position(), projects_bar.length()), (0, Some(3)));
+        for _ in 0..2 {
+            displ.handle(IncreaseProjectsProgress, &mut context()).await;
+        }
+        assert_eq!(projects_bar.position(), 2);
+        assert!(!projects_bar.is_finished());
+        displ.handle(IncreaseProjectsProgress, &mut context()).await;
+        assert!(projects_bar.is_finished());
+        assert_eq!(displ.progress_bars["jobs"].position(), 0);
+    }
+}
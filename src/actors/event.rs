@@ -1,9 +1,102 @@
-use tiny_tokio_actor::SystemEvent;
+use std::io::{Error, Write};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tiny_tokio_actor::{EventReceiver, SystemEvent};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::git::EraseOutcome;
 
 /// Event used in the event bus of the system.
-#[derive(Clone, Debug)]
-pub struct Event(String);
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A project was resolved from the searched term.
+    ProjectResolved { project_id: u64 },
+    /// A page of items was fetched, `count` being the number of matching items.
+    PageFetched { target: &'static str, project_id: u64, page: u64, count: usize },
+    /// An item was erased.
+    Erased { target: &'static str, project_id: u64, id: u64 },
+    /// An item was skipped, e.g. already gone when erased.
+    Skipped { target: &'static str, project_id: u64, id: u64 },
+    /// An item could not be erased.
+    Failed { target: &'static str, project_id: u64, id: u64, error: String },
+}
 
-/// Implement the `SystemEvent` trait for the `Event` struct.
+/// Implement the `SystemEvent` trait for the `Event` enum.
 impl SystemEvent for Event {}
 
+impl Event {
+    /// The event matching the result of an erase request.
+    pub fn from_erase(target: &'static str, project_id: u64, id: u64, result: &Result<EraseOutcome, Error>) -> Self {
+        match result {
+            Ok(EraseOutcome::Erased | EraseOutcome::Accepted) => Event::Erased { target, project_id, id },
+            Ok(EraseOutcome::AlreadyGone) => Event::Skipped { target, project_id, id },
+            Err(err) => Event::Failed { target, project_id, id, error: err.to_string() },
+        }
+    }
+}
+
+/// A log line of an event, with the time it was logged at.
+#[derive(Serialize)]
+struct LogLine<'a> {
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// Subscriber writing each event of the bus as a JSON log line on stderr, until the bus is closed.
+pub async fn log_json(events: EventReceiver<Event>) {
+    write_ndjson(events, std::io::stderr()).await
+}
+
+/// Subscriber writing each event of the bus as a JSON line to the given sink, until the bus is closed.
+pub async fn write_ndjson<W: Write>(mut events: EventReceiver<Event>, mut sink: W) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let line = LogLine { timestamp: Utc::now(), event: &event };
+                let _ = writeln!(sink, "{}", serde_json::to_string(&line).unwrap());
+            }
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use tiny_tokio_actor::EventBus;
+
+    use super::*;
+
+    #[test]
+    fn from_erase_matches_the_outcome() {
+        let event = |result| serde_json::to_value(Event::from_erase("jobs", 1, 2, &result)).unwrap()["event"].clone();
+        assert_eq!(event(Ok(EraseOutcome::Erased)), "erased");
+        assert_eq!(event(Ok(EraseOutcome::Accepted)), "erased");
+        assert_eq!(event(Ok(EraseOutcome::AlreadyGone)), "skipped");
+        assert_eq!(event(Err(Error::new(ErrorKind::PermissionDenied, "refused"))), "failed");
+    }
+
+    #[tokio::test]
+    async fn write_ndjson_writes_a_line_per_event() {
+        let bus = EventBus::<Event>::new(10);
+        let events = bus.subscribe();
+        bus.send(Event::ProjectResolved { project_id: 7 }).unwrap();
+        bus.send(Event::Failed { target: "jobs", project_id: 7, id: 3, error: "refused".to_string() }).unwrap();
+        drop(bus);
+        let mut sink = Vec::new();
+        write_ndjson(events, &mut sink).await;
+        let lines: Vec<serde_json::Value> = String::from_utf8(sink).unwrap().lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "project_resolved");
+        assert_eq!(lines[0]["project_id"], 7);
+        assert!(lines[0]["timestamp"].is_string());
+        assert_eq!(lines[1]["error"], "refused");
+    }
+}
@@ -1,7 +1,11 @@
-use std::{env::var, io::{Error, ErrorKind}};
+use std::{collections::HashMap, env::var, fs, io::{Error, ErrorKind}, path::PathBuf, sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use tiny_tokio_actor::{Actor, ActorContext, async_trait, Handler, Message};
+use clap::ValueEnum;
+use reqwest::{header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE, DATE, LINK}, Certificate, Method, Request, RequestBuilder, Response, StatusCode, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tiny_tokio_actor::{Actor, ActorContext, ActorError, ActorPath, ActorRef, ActorSystem, async_trait, Handler, Message};
+
+use crate::clock::{Clock, SystemClock};
 
 use super::event::Event;
 
@@ -13,8 +17,112 @@ use super::event::Event;
 pub struct Git {
     /// The token used to authenticate to the Gitlab API.
     pub token: String,
+    /// Whether whitespace around the token, e.g. the trailing newline of a command output, was trimmed.
+    pub token_trimmed: bool,
+    /// The base url of the Gitlab API.
+    pub base_url: String,
+    /// Additional headers sent along with every request.
+    pub headers: Vec<(String, String)>,
+    /// The http client shared by all the requests.
+    pub client: reqwest::Client,
+    /// Whether each request is printed as an equivalent curl command before being sent.
+    pub dump_requests: bool,
+    /// The number of times a request failing to connect is retried.
+    pub connect_retries: u32,
+    /// The timeout of the erase requests, overriding the one of the client.
+    pub erase_timeout: Option<Duration>,
+    /// The bounds of the waits between the retries of a request.
+    pub backoff: Backoff,
+    /// The number of remaining requests of the rate limit under which the requests are paused until its reset.
+    pub rate_limit_threshold: Option<u64>,
+    /// The instant until which the requests are paused, the rate limit being nearly reached.
+    /// Like the other state below, it is shared by the clones of the actor, e.g. the actors of a pool.
+    pub pause_until: Arc<Mutex<Option<Instant>>>,
+    /// The metrics of the requests sent so far.
+    pub metrics: Arc<Mutex<RequestMetrics>>,
+    /// The links to the next keyset pages of jobs, by project and page number.
+    pub keyset_links: Arc<Mutex<HashMap<(u64, u64), String>>>,
+    /// The ids of the projects resolved from their name and group.
+    pub project_cache: Arc<Mutex<ProjectCache>>,
+    /// The clock the pauses on the rate limit, the waits between the retries and the expiry of the cached projects
+    /// are timed with.
+    pub clock: Arc<dyn Clock>
+}
+
+/// Pool of Git actors cloned from the same one, sharing its client and its state. An actor handling its messages one
+/// at a time, the messages asked concurrently are spread over the actors of the pool so that their requests are sent
+/// concurrently, each message being asked to the actor with the fewest messages pending.
+#[derive(Clone)]
+pub struct GitPool {
+    /// The actors of the pool.
+    pub actors: Vec<ActorRef<Event, Git>>,
+    /// The number of messages asked to each actor and not answered yet.
+    pub pending: Arc<Vec<AtomicUsize>>,
+}
+
+impl GitPool {
+    /// Creates a pool of `size` actors, at least one, cloned from the given actor and named after `name`.
+    pub async fn create(system: &ActorSystem<Event>, name: &str, git: Git, size: usize) -> Result<Self, ActorError> {
+        let mut actors = Vec::new();
+        for index in 0..size.max(1) {
+            actors.push(system.create_actor(&format!("{}-{}", name, index), git.clone()).await?);
+        }
+        Ok(GitPool { pending: Arc::new(actors.iter().map(|_| AtomicUsize::new(0)).collect()), actors })
+    }
+
+    /// Asks a message to the actor of the pool with the fewest messages pending.
+    pub async fn ask<M>(&self, msg: M) -> Result<M::Response, ActorError>
+    where
+        M: Message,
+        Git: Handler<Event, M> {
+        let index = (0..self.actors.len())
+            .min_by_key(|index| self.pending[*index].load(Ordering::SeqCst))
+            .unwrap_or(0);
+        self.pending[index].fetch_add(1, Ordering::SeqCst);
+        let response = self.actors[index].ask(msg).await;
+        self.pending[index].fetch_sub(1, Ordering::SeqCst);
+        response
+    }
+
+    /// The paths of the actors of the pool.
+    pub fn paths(&self) -> Vec<ActorPath> {
+        self.actors.iter().map(|actor| actor.path().clone()).collect()
+    }
+}
+
+/// Configuration used to create the Git actor.
+#[derive(Clone, Debug, Default)]
+pub struct GitConfig {
     /// The base url of the Gitlab API.
-    pub base_url: String
+    pub base_url: String,
+    /// Additional headers sent along with every request.
+    pub headers: Vec<(String, String)>,
+    /// Whether invalid certificates are accepted.
+    pub insecure: bool,
+    /// The path of an additional PEM root certificate to trust.
+    pub ca_cert: Option<PathBuf>,
+    /// Whether each request is printed as an equivalent curl command before being sent.
+    pub dump_requests: bool,
+    /// The number of times a request failing to connect is retried.
+    pub connect_retries: u32,
+    /// The timeout of every request, none when not set.
+    pub timeout: Option<Duration>,
+    /// The timeout of the erase requests, overriding `timeout`.
+    pub erase_timeout: Option<Duration>,
+    /// The bounds of the waits between the retries of a request.
+    pub backoff: Backoff,
+    /// The number of remaining requests of the rate limit under which the requests are paused until its reset.
+    pub rate_limit_threshold: Option<u64>,
+    /// The maximum number of idle connections kept per host by the client, unbounded when not set.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// The name of the environment variable holding the token, `GITLAB_TOKEN` when not set.
+    pub token_env: Option<String>,
+    /// The path of the API appended to the base url when missing, `/api/v4` when not set.
+    pub api_path: Option<String>,
+    /// How long a resolved project id is kept before being resolved again, forever when not set.
+    pub project_cache_ttl: Option<Duration>,
+    /// The clock the pauses and the waits are timed with, the system clock when not set.
+    pub clock: Option<Arc<dyn Clock>>
 }
 
 /// Git actor implementation.
@@ -24,17 +132,387 @@ impl Actor<Event> for Git {}
 /// Default implementation for the Git actor.
 impl Default for Git {
     fn default() -> Self {
-        Git {
-            token : var("GITLAB_TOKEN").unwrap(),
-            base_url : var("GITLAB_URL").unwrap()
+        Git::new(GitConfig {
+            base_url: var("GITLAB_URL").unwrap(),
+            ..GitConfig::default()
+        }).unwrap()
+    }
+}
+
+impl Git {
+    /// Creates a new Git actor from the given configuration, reading the token from the configured environment variable.
+    /// `${VAR}` references in the base url and header values are expanded from the environment.
+    pub fn new(config: GitConfig) -> Result<Self, Error> {
+        let headers = config.headers.into_iter()
+            .map(|(name, value)| expand_env(&value).map(|value| (name, value)))
+            .collect::<Result<Vec<(String, String)>, Error>>()?;
+        // The compressed responses are requested with `Accept-Encoding` and transparently decompressed,
+        // the pages of jobs being large.
+        let mut builder = reqwest::Client::builder()
+            .gzip(true)
+            .deflate(true)
+            .brotli(true)
+            .danger_accept_invalid_certs(config.insecure);
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(max_idle) = config.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(path) = config.ca_cert {
+            let pem = fs::read(&path)
+                .map_err(|err| Error::new(err.kind(), format!("Could not read the CA certificate {}: {}", path.display(), err)))?;
+            let certificate = Certificate::from_pem(&pem)
+                .map_err(|err| Error::new(ErrorKind::InvalidData, format!("Invalid CA certificate {}: {}", path.display(), err)))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        let token_env = config.token_env.as_deref().unwrap_or("GITLAB_TOKEN");
+        let raw_token = var(token_env).map_err(|_| Error::new(ErrorKind::NotFound, format!("The {} environment variable is not set.", token_env)))?;
+        let token = raw_token.trim().to_string();
+        if token.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("The {} environment variable is empty.", token_env)));
+        }
+        Ok(Git {
+            token_trimmed: token != raw_token,
+            token,
+            base_url : api_url(&validate_base_url(&expand_env(&config.base_url)?)?, config.api_path.as_deref()),
+            headers,
+            client: builder.build().map_err(|err| Error::other(err.to_string()))?,
+            dump_requests: config.dump_requests,
+            connect_retries: config.connect_retries,
+            erase_timeout: config.erase_timeout,
+            backoff: config.backoff,
+            rate_limit_threshold: config.rate_limit_threshold,
+            pause_until: Arc::new(Mutex::new(None)),
+            metrics: Arc::new(Mutex::new(RequestMetrics::default())),
+            keyset_links: Arc::new(Mutex::new(HashMap::new())),
+            project_cache: Arc::new(Mutex::new(ProjectCache::new(config.project_cache_ttl))),
+            clock: config.clock.unwrap_or(Arc::new(SystemClock))
+        })
+    }
+
+    /// Prepares a request to the Gitlab API with the authentication and additional headers.
+    fn request(&self, method: Method, url: String) -> RequestBuilder {
+        let mut request = self.client
+            .request(method, url)
+            .header("PRIVATE-TOKEN", self.token.clone());
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request
+    }
+
+    /// Sends a request prepared with `request`, dumping it first when asked to, and records its metrics.
+    /// A request failing to connect, e.g. refused or with the connections exhausted, is retried with a growing backoff,
+    /// until the retries or the total wait allowed by the backoff are exhausted.
+    async fn send(&mut self, request: RequestBuilder) -> Result<Response, Error> {
+        let mut request = request.build().map_err(Error::other)?;
+        if self.dump_requests {
+            eprintln!("{}", curl_command(&request));
+        }
+        let mut attempt = 0;
+        let mut waited = Duration::ZERO;
+        loop {
+            // A request with a streamed body cannot be cloned, hence not retried.
+            let retry = if attempt < self.connect_retries { request.try_clone() } else { None };
+            let start = Instant::now();
+            let res = self.client.execute(request).await;
+            self.metrics.lock().unwrap().record(start.elapsed());
+            if let (Ok(res), Some(threshold)) = (&res, self.rate_limit_threshold) {
+                let header = |name: &str| res.headers()
+                    .get(name)
+                    .and_then(|x| x.to_str().ok())
+                    .and_then(|x| x.parse::<i64>().ok());
+                if let (Some(remaining), Some(reset)) = (header("ratelimit-remaining"), header("ratelimit-reset")) {
+                    if remaining <= threshold as i64 {
+                        // The reset is a date of the Gitlab instance, so the pause is measured from the date of the
+                        // response, neither the skew of the local clock nor a pretended date shifting it.
+                        let sent_at = response_date(res.headers()).unwrap_or_else(Utc::now);
+                        let pause = Duration::from_secs(reset.saturating_sub(sent_at.timestamp()).max(0) as u64);
+                        *self.pause_until.lock().unwrap() = Some(self.clock.instant() + pause);
+                    }
+                }
+            }
+            match (res, retry) {
+                (Err(err), Some(retry)) if err.is_connect() => {
+                    attempt += 1;
+                    let delay = self.backoff.next(Duration::from_millis(200 << attempt.min(6)), waited)
+                        .ok_or_else(|| self.backoff.timeout_error(waited, &err))?;
+                    self.clock.sleep(delay).await;
+                    waited += delay;
+                    request = retry;
+                }
+                (res, _) => return res.map_err(Error::other),
+            }
+        }
+    }
+
+    /// Parses the JSON body of a response, with a clear error when an HTML page was returned instead,
+    /// which happens when the base url points at the web UI rather than the API. The bytes read are added to the metrics,
+    /// the content length being unknown for the chunked or compressed responses.
+    async fn parse_json<T: DeserializeOwned>(&mut self, res: Response) -> Result<T, Error> {
+        let is_html = res.headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/html"));
+        if is_html {
+            return Err(Error::new(ErrorKind::InvalidData, format!(
+                "The Gitlab url returned an HTML page for {}: it looks like you pointed at the web UI, not the API (e.g. https://gitlab.example.com/api/v4).",
+                res.url())));
+        }
+        let url = res.url().clone();
+        let bytes = res.bytes().await.map_err(|err| Error::other(err.to_string()))?;
+        self.metrics.lock().unwrap().bytes += bytes.len() as u64;
+        serde_json::from_slice::<T>(&bytes).map_err(|err| Error::new(ErrorKind::InvalidData, format!(
+            "Invalid response from {}: {} in {}", url, err, body_excerpt(&bytes))))
+    }
+
+    /// Searches the projects matching the term, within the group if any, at most `limit` of them.
+    async fn search_projects(&mut self, term: &str, group: Option<String>, limit: Option<u64>) -> Result<Vec<Project>, Error> {
+        let mut request = self
+            .request(Method::GET,
+                if let Some(group) = group {
+                    format!("{}/groups/{}/projects", self.base_url, group)
+                } else {
+                    format!("{}/projects", self.base_url)
+                }
+            )
+            .query(&[("search", term), ("scope", "projects")]);
+        if let Some(limit) = limit {
+            request = request.query(&[("per_page", limit)]);
+        }
+        let res = self.send(request).await
+            .map_err(|err| Error::new(err.kind(), format!("Search request failed: {}", err)))?;
+        self.parse_json(check_list_status(res)?).await
+    }
+}
+
+/// The bounds of the waits between the retries of a request, unbounded by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Backoff {
+    /// The longest wait before a single retry, the growing backoff being capped to it.
+    pub max_delay: Option<Duration>,
+    /// The longest cumulative wait over all the retries, after which retrying is given up.
+    pub max_total_wait: Option<Duration>,
+}
+
+impl Backoff {
+    /// Returns the wait before the next retry given its uncapped `delay` and the time already `waited`,
+    /// none when waiting it would exceed the total wait allowed.
+    pub fn next(&self, delay: Duration, waited: Duration) -> Option<Duration> {
+        let delay = self.max_delay.map_or(delay, |max| delay.min(max));
+        match self.max_total_wait {
+            Some(max) if waited + delay > max => None,
+            _ => Some(delay),
+        }
+    }
+
+    /// The error returned once the total wait allowed is exhausted, the last error being `cause`.
+    pub fn timeout_error(&self, waited: Duration, cause: &dyn std::fmt::Display) -> Error {
+        Error::new(ErrorKind::TimedOut, format!(
+            "Gave up retrying after waiting {}s in total (--max-total-wait-secs): {}", waited.as_secs(), cause
+        ))
+    }
+}
+
+/// Renders a request as an equivalent curl command, with the authentication headers redacted.
+pub fn curl_command(request: &Request) -> String {
+    let mut command = format!("curl -X {} '{}'", request.method(), request.url());
+    for (name, value) in request.headers() {
+        let value = if name == "private-token" || name == AUTHORIZATION {
+            "[REDACTED]"
+        } else {
+            value.to_str().unwrap_or("[BINARY]")
+        };
+        command.push_str(&format!(" -H '{}: {}'", name, value));
+    }
+    command
+}
+
+/// Cache of the ids of the projects resolved from their name and group, whose entries expire after the TTL if any.
+#[derive(Clone, Debug, Default)]
+pub struct ProjectCache {
+    /// How long an entry is kept, forever when not set.
+    pub ttl: Option<Duration>,
+    /// The id of each project by name and group, with the instant it was resolved at.
+    pub entries: HashMap<(String, Option<String>), (u64, Instant)>
+}
+
+impl ProjectCache {
+    /// Creates an empty cache whose entries expire after the given TTL, if any.
+    pub fn new(ttl: Option<Duration>) -> Self {
+        ProjectCache { ttl, entries: HashMap::new() }
+    }
+
+    /// The id of the project cached at `now`, none when missing or expired.
+    pub fn get(&self, name: &str, group: &Option<String>, now: Instant) -> Option<u64> {
+        self.entries.get(&(name.to_string(), group.clone()))
+            .filter(|(_, resolved_at)| self.ttl.is_none_or(|ttl| now.duration_since(*resolved_at) < ttl))
+            .map(|(id, _)| *id)
+    }
+
+    /// Caches the id of the project resolved at `now`.
+    pub fn insert(&mut self, name: &str, group: &Option<String>, id: u64, now: Instant) {
+        self.entries.insert((name.to_string(), group.clone()), (id, now));
+    }
+}
+
+/// Metrics of the requests sent to the Gitlab API.
+#[derive(Clone, Debug, Default)]
+pub struct RequestMetrics {
+    /// The latency of each request, until the response headers were received.
+    pub latencies: Vec<Duration>,
+    /// The total bytes of the response bodies read.
+    pub bytes: u64
+}
+
+impl RequestMetrics {
+    /// Records a request with its latency, its bytes being added once its body is read.
+    pub fn record(&mut self, latency: Duration) {
+        self.latencies.push(latency);
+    }
+
+    /// The number of requests recorded.
+    pub fn count(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// The average latency of the requests.
+    pub fn average(&self) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32
+    }
+
+    /// The latency under which the given ratio (e.g. 0.95) of the requests fall, using the nearest rank.
+    pub fn percentile(&self, ratio: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        let rank = (ratio * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+}
+
+/// Validates that the base url is an absolute http(s) url, so that no request is built from a relative one.
+/// The path prefix of an instance mounted under a sub-path (e.g. `https://host/gitlab`) is kept, only its empty
+/// segments, the query and the fragment are dropped, so that the urls built from it have no double slash.
+pub fn validate_base_url(base_url: &str) -> Result<String, Error> {
+    match Url::parse(base_url) {
+        Ok(mut url) if url.scheme() == "http" || url.scheme() == "https" => {
+            let path = url.path().split('/').filter(|segment| !segment.is_empty()).collect::<Vec<&str>>().join("/");
+            url.set_path(&path);
+            url.set_query(None);
+            url.set_fragment(None);
+            Ok(url.as_str().trim_end_matches('/').to_string())
         }
+        _ => Err(Error::new(ErrorKind::InvalidInput, format!(
+            "The Gitlab url '{}' must be an absolute http(s) url, e.g. https://gitlab.example.com/api/v4. Check GITLAB_URL or --gitlab-url.",
+            base_url))),
+    }
+}
+
+/// Appends the path of the API to the base url, unless it already ends with it.
+pub fn api_url(base_url: &str, api_path: Option<&str>) -> String {
+    let api_path = api_path.unwrap_or("/api/v4").trim_matches('/');
+    if api_path.is_empty() || base_url.ends_with(&format!("/{}", api_path)) {
+        base_url.to_string()
+    } else {
+        format!("{}/{}", base_url, api_path)
+    }
+}
+
+/// Checks the status of the response to a listing request. A 404 is reported as `NotFound`, the project lacking
+/// the feature listed (e.g. no package registry) or the endpoint missing.
+pub fn check_list_status(res: Response) -> Result<Response, Error> {
+    if res.status() == StatusCode::NOT_FOUND {
+        return Err(Error::new(ErrorKind::NotFound, format!("The listing {} was not found (404 Not Found).", res.url())));
+    }
+    res.error_for_status().map_err(|err| Error::other(err.to_string()))
+}
+
+/// Checks the status of the response to a write request. A 403 caused by a token lacking a scope is turned into
+/// an actionable error naming the scope needed to `action`, e.g. `erase jobs`.
+/// Any success status is accepted as is, notably the 204 No Content of the deletions whose empty body is never read.
+pub async fn check_write_status(res: Response, action: &str) -> Result<Response, Error> {
+    if res.status().is_success() {
+        return Ok(res);
+    }
+    if res.status() != StatusCode::FORBIDDEN {
+        return res.error_for_status().map_err(|err| Error::other(err.to_string()));
+    }
+    let url = res.url().clone();
+    let bytes = res.bytes().await.unwrap_or_default();
+    let body = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default();
+    let text = |field: &str| body.get(field).and_then(|value| value.as_str()).unwrap_or_default().to_string();
+    if text("error") == "insufficient_scope" || text("message").contains("scope") || text("error_description").contains("scope") {
+        let scope = Some(text("scope")).filter(|scope| !scope.is_empty()).unwrap_or("api".to_string());
+        return Err(Error::new(ErrorKind::PermissionDenied, format!(
+            "Your token needs the `{}` scope to {}: the Gitlab API refused {} with an insufficient scope.", scope, action, url)));
+    }
+    Err(Error::new(ErrorKind::PermissionDenied, format!("The Gitlab API refused {} (403 Forbidden): {}", url, body_excerpt(&bytes))))
+}
+
+/// The date of a response from its `Date` header, if valid.
+pub fn response_date(headers: &HeaderMap) -> Option<DateTime<Utc>> {
+    headers.get(DATE)
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// The link to the next page announced by the `Link` header of a response, if any.
+pub fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers.get(LINK)
+        .and_then(|value| value.to_str().ok())?
+        .split(',')
+        .find(|part| part.contains("rel=\"next\""))
+        .and_then(|part| Some(part.split_once('<')?.1.split_once('>')?.0.to_string()))
+}
+
+/// The maximum number of characters of a response body quoted in an error.
+const BODY_EXCERPT_LEN: usize = 200;
+
+/// Renders the start of a response body for an error, lossily decoded as it may be binary or not UTF-8,
+/// the control characters being replaced so as not to garble the terminal.
+fn body_excerpt(bytes: &[u8]) -> String {
+    let body = String::from_utf8_lossy(bytes);
+    let mut excerpt: String = body.chars()
+        .take(BODY_EXCERPT_LEN)
+        .map(|c| if c.is_control() { char::REPLACEMENT_CHARACTER } else { c })
+        .collect();
+    if body.chars().count() > BODY_EXCERPT_LEN {
+        excerpt.push_str("...");
+    }
+    excerpt
+}
+
+/// Expands the `${VAR}` references of the given value with the matching environment variables.
+/// Fails if a referenced variable is not defined.
+pub fn expand_env(value: &str) -> Result<String, Error> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Unterminated variable reference in '{}'.", value)))?;
+        let name = &rest[start + 2..start + end];
+        let resolved = var(name)
+            .map_err(|_| Error::new(ErrorKind::NotFound, format!("The environment variable {} referenced in '{}' is not defined.", name, value)))?;
+        expanded.push_str(&resolved);
+        rest = &rest[start + end + 1..];
     }
+    expanded.push_str(rest);
+    Ok(expanded)
 }
 
 /// ------------------------------ ///
 /// ---------- Messages ---------- ///
 /// ------------------------------ ///
-
+///
 /// ---------- Get Project ---------- ///
 /// Message used to get the projects from the Gitlab API.
 #[derive(Clone)]
@@ -55,51 +533,331 @@ impl Message for GetProject {
 /// Handler for the GetProjects message for the Git actor.
 #[async_trait]
 impl Handler<Event, GetProject> for Git {
-    async fn handle(&mut self, msg: GetProject, _ctx: &mut ActorContext<Event>) -> Result<u64, Error> {
-        let client = reqwest::Client::new();
-        let res = client
-            .get(
-                if let Some(group) = msg.project_group {
-                    format!("{}/groups/{}/projects", self.base_url, group)
-                } else {
-                    format!("{}/projects", self.base_url)
-                }
-            )
-            .header("PRIVATE-TOKEN", self.token.clone())
-            .query(&[("search", msg.project_name), ("scope", "projects".to_string())])
-            .send().await;
-        match res {
-            Ok(res) => {
-                let projects: Vec<Project> = res.json().await.unwrap();
-
-                match projects.len() {
-                    0 => {
-                        return Err(Error::new(ErrorKind::NotFound, "No project found that matches the researched term."));
-                    },
-                    1 => {
-                        return Ok(projects[0].id);
-                    },
-                    _ => {
-                        return Err(Error::new(ErrorKind::Unsupported, "Multiple projects found that matches the researched term. You might want to specify the group or add some more characters."));
-                    }
-                }
-            }
-            Err(_) => {
-                return Err(Error::new(ErrorKind::Other, "Search request failed."));
-            }
+    async fn handle(&mut self, msg: GetProject, ctx: &mut ActorContext<Event>) -> Result<u64, Error> {
+        let cached = self.project_cache.lock().unwrap().get(&msg.project_name, &msg.project_group, self.clock.instant());
+        if let Some(id) = cached {
+            return Ok(id);
+        }
+        let projects = self.search_projects(&msg.project_name, msg.project_group.clone(), None).await?;
+        // Among several matches, the project named exactly as searched is picked.
+        let exact: Vec<&Project> = projects.iter().filter(|project| project.name == msg.project_name).collect();
+        let project = match (projects.len(), exact.len()) {
+            (0, _) => return Err(Error::new(ErrorKind::NotFound, "No project found that matches the researched term.")),
+            (1, _) => &projects[0],
+            (_, 1) => exact[0],
+            _ => return Err(Error::new(ErrorKind::Unsupported, "Multiple projects found that matches the researched term. You might want to specify the group, add some more characters or pick it with --interactive.")),
+        };
+        ctx.system.publish(Event::ProjectResolved { project_id: project.id });
+        self.project_cache.lock().unwrap().insert(&msg.project_name, &msg.project_group, project.id, self.clock.instant());
+        Ok(project.id)
+    }
+}
+
+/// ---------- Search Projects ---------- ///
+/// Message used to list the projects matching a search term, for the user to pick one.
+#[derive(Clone)]
+pub struct SearchProjects {
+    /// The name of the project to search for.
+    pub project_name: String,
+    /// The group of the project to search for.
+    pub project_group: Option<String>,
+    /// The maximum number of projects listed.
+    pub limit: u64
+}
+
+/// SearchProjects message implementation.
+impl Message for SearchProjects {
+    /// The type of the result.
+    /// A result that contains either the matching projects or an error.
+    type Response = Result<Vec<Project>, Error>;
+}
+
+/// Handler for the SearchProjects message for the Git actor.
+#[async_trait]
+impl Handler<Event, SearchProjects> for Git {
+    async fn handle(&mut self, msg: SearchProjects, _ctx: &mut ActorContext<Event>) -> Result<Vec<Project>, Error> {
+        self.search_projects(&msg.project_name, msg.project_group, Some(msg.limit)).await
+    }
+}
+
+/// ---------- List Group Projects ---------- ///
+/// Message used to get a page of the projects of a group, including its subgroups, from the Gitlab API.
+#[derive(Clone)]
+pub struct ListGroupProjects {
+    /// The group to list the projects of.
+    pub group: String,
+    /// The page of the projects to get.
+    pub page: u64
+}
+
+/// ListGroupProjectsResponse structure that holds the response of the ListGroupProjects message.
+pub struct ListGroupProjectsResponse {
+    /// The projects that were found.
+    pub projects: Vec<Project>,
+    /// The next page of projects to get.
+    pub next_page: Option<u64>
+}
+
+/// ListGroupProjects message implementation.
+impl Message for ListGroupProjects {
+    /// The type of the result.
+    /// A result that contains either the projects of the page or an error.
+    type Response = Result<ListGroupProjectsResponse, Error>;
+}
+
+/// Handler for the ListGroupProjects message for the Git actor.
+#[async_trait]
+impl Handler<Event, ListGroupProjects> for Git {
+    async fn handle(&mut self, msg: ListGroupProjects, _ctx: &mut ActorContext<Event>) -> Result<ListGroupProjectsResponse, Error> {
+        let request = self
+            .request(Method::GET, format!("{}/groups/{}/projects", self.base_url, msg.group))
+            .query(&[("per_page", "50"), ("page", &msg.page.to_string()), ("include_subgroups", "true")]);
+        let res = self.send(request).await
+            .map_err(|err| Error::other(err.to_string()))?
+            .error_for_status()
+            .map_err(|err| Error::other(err.to_string()))?;
+        let next_page = res.headers()
+            .get("x-next-page")
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.parse::<u64>().ok());
+        Ok(ListGroupProjectsResponse {
+            projects: self.parse_json(res).await?,
+            next_page
+        })
+    }
+}
+
+/// ---------- Get Project By Id ---------- ///
+/// Message used to check that a project exists and is accessible with the token, given its id.
+#[derive(Clone)]
+pub struct GetProjectById {
+    /// The id of the project.
+    pub project_id: u64
+}
+
+/// GetProjectById message implementation.
+impl Message for GetProjectById {
+    /// The type of the result.
+    /// A result that contains either the project or an error.
+    type Response = Result<Project, Error>;
+}
+
+/// Handler for the GetProjectById message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetProjectById> for Git {
+    async fn handle(&mut self, msg: GetProjectById, _ctx: &mut ActorContext<Event>) -> Result<Project, Error> {
+        let request = self
+            .request(Method::GET, format!("{}/projects/{}", self.base_url, msg.project_id));
+        let res = self.send(request).await?;
+        match res.status() {
+            StatusCode::NOT_FOUND | StatusCode::FORBIDDEN => Err(Error::new(ErrorKind::NotFound, format!(
+                "No project with the id {} exists, or the token cannot access it.", msg.project_id
+            ))),
+            _ => self.parse_json(res.error_for_status().map_err(|err| Error::other(err.to_string()))?).await,
         }
     }
 }
 
+/// ---------- Get Version ---------- ///
+/// Message used to get the version of the Gitlab instance.
+#[derive(Clone)]
+pub struct GetVersion;
+
+/// GetVersion message implementation.
+impl Message for GetVersion {
+    /// The type of the result.
+    /// A result that contains either the version of the instance or an error.
+    type Response = Result<GitlabVersion, Error>;
+}
+
+/// Handler for the GetVersion message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetVersion> for Git {
+    async fn handle(&mut self, _msg: GetVersion, _ctx: &mut ActorContext<Event>) -> Result<GitlabVersion, Error> {
+        let request = self
+            .request(Method::GET, format!("{}/version", self.base_url));
+        let res = self.send(request).await
+            .map_err(|err| Error::other(err.to_string()))?
+            .error_for_status()
+            .map_err(|err| Error::other(err.to_string()))?;
+        self.parse_json::<GitlabVersion>(res).await
+    }
+}
+
+/// ---------- Get Server Time ---------- ///
+/// Message used to get the current date of the Gitlab instance, from the `Date` header of its response.
+#[derive(Clone)]
+pub struct GetServerTime;
+
+/// GetServerTime message implementation.
+impl Message for GetServerTime {
+    /// The type of the result.
+    /// A result that contains either the date of the instance or an error.
+    type Response = Result<DateTime<Utc>, Error>;
+}
+
+/// Handler for the GetServerTime message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetServerTime> for Git {
+    async fn handle(&mut self, _msg: GetServerTime, _ctx: &mut ActorContext<Event>) -> Result<DateTime<Utc>, Error> {
+        let request = self
+            .request(Method::GET, format!("{}/version", self.base_url));
+        let res = self.send(request).await?;
+        response_date(res.headers())
+            .ok_or(Error::new(ErrorKind::InvalidData, "The Gitlab instance did not send a valid Date header."))
+    }
+}
+
+/// ---------- Get User ---------- ///
+/// Message used to get the user authenticated by the token.
+#[derive(Clone)]
+pub struct GetUser;
+
+/// GetUser message implementation.
+impl Message for GetUser {
+    /// The type of the result.
+    /// A result that contains either the authenticated user or an error.
+    type Response = Result<User, Error>;
+}
+
+/// Handler for the GetUser message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetUser> for Git {
+    async fn handle(&mut self, _msg: GetUser, _ctx: &mut ActorContext<Event>) -> Result<User, Error> {
+        let request = self.request(Method::GET, format!("{}/user", self.base_url));
+        let res = self.send(request).await
+            .map_err(|err| Error::other(err.to_string()))?
+            .error_for_status()
+            .map_err(|err| Error::other(err.to_string()))?;
+        self.parse_json::<User>(res).await
+    }
+}
+
+/// ---------- Check Endpoint ---------- ///
+/// Message used to check that a listing endpoint of the Gitlab API is reachable, without modifying anything.
+#[derive(Clone)]
+pub struct CheckEndpoint {
+    /// The path of the endpoint, relative to the base url, e.g. `/projects/1/jobs`.
+    pub path: String
+}
+
+/// CheckEndpoint message implementation.
+impl Message for CheckEndpoint {
+    /// The type of the result.
+    /// A result that contains either nothing when the endpoint is reachable or an error.
+    type Response = Result<(), Error>;
+}
+
+/// Handler for the CheckEndpoint message for the Git actor.
+#[async_trait]
+impl Handler<Event, CheckEndpoint> for Git {
+    async fn handle(&mut self, msg: CheckEndpoint, _ctx: &mut ActorContext<Event>) -> Result<(), Error> {
+        let request = self
+            .request(Method::GET, format!("{}{}", self.base_url, msg.path))
+            .query(&[("per_page", "1")]);
+        self.send(request).await
+            .map_err(|err| Error::other(err.to_string()))?
+            .error_for_status()
+            .map_err(|err| Error::other(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// ---------- Post Webhook ---------- ///
+/// Message used to post a JSON payload to a webhook, outside of the Gitlab API and without its token.
+#[derive(Clone)]
+pub struct PostWebhook {
+    /// The url of the webhook.
+    pub url: String,
+    /// The JSON payload to post.
+    pub payload: serde_json::Value
+}
+
+/// PostWebhook message implementation.
+impl Message for PostWebhook {
+    /// The type of the result.
+    /// A result that contains either nothing or an error.
+    type Response = Result<(), Error>;
+}
+
+/// Handler for the PostWebhook message for the Git actor.
+#[async_trait]
+impl Handler<Event, PostWebhook> for Git {
+    async fn handle(&mut self, msg: PostWebhook, _ctx: &mut ActorContext<Event>) -> Result<(), Error> {
+        // Sent with the bare client, the webhook must not receive the token nor the additional headers.
+        self.client
+            .post(&msg.url)
+            .json(&msg.payload)
+            .send().await
+            .map_err(|err| Error::other(err.to_string()))?
+            .error_for_status()
+            .map_err(|err| Error::other(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// ---------- Get Metrics ---------- ///
+/// Message used to get the metrics of the requests sent so far.
+#[derive(Clone)]
+pub struct GetMetrics;
+
+/// GetMetrics message implementation.
+impl Message for GetMetrics {
+    /// The type of the result.
+    type Response = RequestMetrics;
+}
+
+/// Handler for the GetMetrics message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetMetrics> for Git {
+    async fn handle(&mut self, _msg: GetMetrics, _ctx: &mut ActorContext<Event>) -> RequestMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+}
+
+/// ---------- Get Pause ---------- ///
+/// Message used to get the instant until which the requests should be paused, the rate limit being nearly reached.
+#[derive(Clone)]
+pub struct GetPause;
+
+/// GetPause message implementation.
+impl Message for GetPause {
+    /// The type of the result.
+    type Response = Option<Instant>;
+}
+
+/// Handler for the GetPause message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetPause> for Git {
+    async fn handle(&mut self, _msg: GetPause, _ctx: &mut ActorContext<Event>) -> Option<Instant> {
+        self.pause_until.lock().unwrap().filter(|until| *until > self.clock.instant())
+    }
+}
+
 /// ---------- Get Jobs ---------- ///
+/// The number of jobs listed per page, unless bounded lower.
+pub const JOBS_PER_PAGE: u64 = 50;
+
 #[derive(Clone)]
 pub struct GetJobs {
     /// The id of the project to get the jobs from.
     pub project_id: u64,
-    /// The date the jobs must be older than.
-    pub older_than: DateTime<Utc>,
+    /// The date the jobs must be created before, if any.
+    pub created_before: Option<DateTime<Utc>>,
+    /// The date the jobs must be created after, if any.
+    pub created_after: Option<DateTime<Utc>>,
+    /// The date of the jobs the window applies to.
+    pub age_field: AgeField,
+    /// Whether the jobs are traversed by id with the keyset pagination, which is not shifted by the jobs
+    /// created meanwhile. The pages are then numbered in order from the first one.
+    pub keyset: bool,
+    /// With the keyset pagination, the id of the job to resume the traversal after, the jobs being then
+    /// traversed from the oldest.
+    pub after_id: Option<u64>,
     /// The page of the jobs to get.
-    pub page: u64
+    pub page: u64,
+    /// The number of jobs per page, at most `JOBS_PER_PAGE` in practice.
+    pub per_page: u64
 }
 
 /// GetJobsResponse structure that holds the response of the GetJobs message.
@@ -107,7 +865,13 @@ pub struct GetJobsResponse {
     /// The jobs that were found.
     pub jobs: Vec<Job>,
     /// The next page of jobs to get.
-    pub next_page: Option<u64>
+    pub next_page: Option<u64>,
+    /// The creation date of the oldest job of the page, before filtering.
+    pub oldest_created_at: Option<DateTime<Utc>>,
+    /// The total number of jobs of the project, from the `x-total` header when present.
+    pub total: Option<u64>,
+    /// The number of jobs of the page, before filtering.
+    pub listed: usize
 }
 
 /// GetJobs message implementation.
@@ -120,82 +884,599 @@ impl Message for GetJobs {
 /// Handler for the GetJobs message for the Git actor.
 #[async_trait]
 impl Handler<Event, GetJobs> for Git {
-    async fn handle(&mut self, msg: GetJobs, _ctx: &mut ActorContext<Event>) -> Result<GetJobsResponse, Error> {
-        let client = reqwest::Client::new();
-        let res = client
-            .get(format!("{}/projects/{}/jobs", self.base_url, msg.project_id))
-            .header("PRIVATE-TOKEN", self.token.clone())
-            .query(&[("per_page", "50"), ("page", &msg.page.to_string())])
-            .send().await;
+    async fn handle(&mut self, msg: GetJobs, ctx: &mut ActorContext<Event>) -> Result<GetJobsResponse, Error> {
+        let link = self.keyset_links.lock().unwrap().remove(&(msg.project_id, msg.page));
+        let request = match link {
+            Some(link) if msg.keyset => self.request(Method::GET, link),
+            _ if msg.keyset => match msg.after_id {
+                Some(after_id) => self
+                    .request(Method::GET, format!("{}/projects/{}/jobs", self.base_url, msg.project_id))
+                    .query(&[("per_page", msg.per_page)])
+                    .query(&[("pagination", "keyset"), ("order_by", "id"), ("sort", "asc")])
+                    .query(&[("id_after", after_id)]),
+                None => self
+                    .request(Method::GET, format!("{}/projects/{}/jobs", self.base_url, msg.project_id))
+                    .query(&[("per_page", msg.per_page)])
+                    .query(&[("pagination", "keyset"), ("order_by", "id"), ("sort", "desc")]),
+            },
+            _ => self
+                .request(Method::GET, format!("{}/projects/{}/jobs", self.base_url, msg.project_id))
+                .query(&[("per_page", msg.per_page), ("page", msg.page)]),
+        };
+        let res = self.send(request).await;
         match res {
             Ok(res) => {
+                let res = check_list_status(res)?;
                 let headers = res.headers().clone();
-                let jobs: Vec<Job> = res.json().await.unwrap();
-                let next_page = headers
-                    .get("x-next-page")
+                let jobs: Vec<Job> = self.parse_json(res).await?;
+                let next_page = if msg.keyset {
+                    next_link(&headers).map(|link| {
+                        self.keyset_links.lock().unwrap().insert((msg.project_id, msg.page + 1), link);
+                        msg.page + 1
+                    })
+                } else {
+                    headers
+                        .get("x-next-page")
+                        .and_then(|x| x.to_str().ok())
+                        .and_then(|x| x.parse::<u64>().ok())
+                };
+                let total = headers
+                    .get("x-total")
                     .and_then(|x| x.to_str().ok())
                     .and_then(|x| x.parse::<u64>().ok());
-                let jobs_to_erase = jobs.iter().filter(|job| job.created_at < msg.older_than && job.erased_at.is_none()).cloned().collect();
+                let oldest_created_at = jobs.iter().map(|job| job.created_at).min();
+                let jobs_to_erase: Vec<Job> = jobs.iter()
+                    .filter(|job| msg.created_before.is_none_or(|date| job.age_date(&msg.age_field) < date))
+                    .filter(|job| msg.created_after.is_none_or(|date| job.age_date(&msg.age_field) > date))
+                    .filter(|job| job.erased_at.is_none())
+                    .cloned().collect();
+                ctx.system.publish(Event::PageFetched { target: "jobs", project_id: msg.project_id, page: msg.page, count: jobs_to_erase.len() });
                 return Ok(GetJobsResponse {
                     jobs: jobs_to_erase,
-                    next_page
+                    next_page,
+                    oldest_created_at,
+                    total,
+                    listed: jobs.len()
                 });
             }
             Err(err) => {
-                return Err(Error::new(ErrorKind::Other, err.to_string()));
+                return Err(Error::other(err.to_string()));
             }
         }
     }
 }
 
-/// ---------- Erase Job ---------- ///
-/// Message used to erase a job from the Gitlab API.
+/// ---------- Count Jobs ---------- ///
+/// Message used to count all the jobs of a project, from the `x-total` header or else by listing all the pages.
 #[derive(Clone)]
-pub struct EraseJob {
-    /// The id of the project to erase the job from.
-    pub project_id: u64,
-    /// The id of the job to erase.
-    pub job_id: u64
+pub struct CountJobs {
+    /// The id of the project to count the jobs of.
+    pub project_id: u64
 }
 
-/// EraseJob message implementation.
-impl Message for EraseJob {
+/// CountJobs message implementation.
+impl Message for CountJobs {
     /// The type of the result.
-    /// A result that contains either nothing or an error.
-    type Response = Result<(), Error>;
+    /// A result that contains either the number of jobs or an error.
+    type Response = Result<u64, Error>;
 }
 
-/// Handler for the EraseJob message for the Git actor.
+/// Handler for the CountJobs message for the Git actor.
 #[async_trait]
-impl Handler<Event, EraseJob> for Git {
-    async fn handle(&mut self, msg: EraseJob, _ctx: &mut ActorContext<Event>) -> Result<(), Error> {
-        let client = reqwest::Client::new();
-        let res = client
-            .post(format!("{}/projects/{}/jobs/{}/erase", self.base_url, msg.project_id, msg.job_id))
-            .header("PRIVATE-TOKEN", self.token.clone())
-            .send().await;
-        match res {
-            Ok(_) => {
-                return Ok(());
-            }
-            Err(err) => {
-                return Err(Error::new(ErrorKind::Other, err.to_string()));
+impl Handler<Event, CountJobs> for Git {
+    async fn handle(&mut self, msg: CountJobs, _ctx: &mut ActorContext<Event>) -> Result<u64, Error> {
+        let mut count = 0;
+        let mut next_page = Some(1);
+        while let Some(page) = next_page {
+            let request = self
+                .request(Method::GET, format!("{}/projects/{}/jobs", self.base_url, msg.project_id))
+                .query(&[("per_page", "100"), ("page", &page.to_string())]);
+            let res = self.send(request).await
+                .map_err(|err| Error::other(err.to_string()))?
+                .error_for_status()
+                .map_err(|err| Error::other(err.to_string()))?;
+            let header = |name: &str| res.headers()
+                .get(name)
+                .and_then(|x| x.to_str().ok())
+                .and_then(|x| x.parse::<u64>().ok());
+            if let Some(total) = header("x-total") {
+                return Ok(total);
             }
+            next_page = header("x-next-page");
+            count += self.parse_json::<Vec<Job>>(res).await?.len() as u64;
         }
+        Ok(count)
     }
 }
 
-/// ---------------------------- ///
-/// ---------- Models ---------- ///
-/// ---------------------------- ///
+/// ---------- Get Job ---------- ///
+/// Message used to get a single job from the Gitlab API.
+#[derive(Clone)]
+pub struct GetJob {
+    /// The id of the project to get the job from.
+    pub project_id: u64,
+    /// The id of the job to get.
+    pub job_id: u64
+}
 
-/// Project model.
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Project {
-    /// The id of the project.
-    pub id: u64,
+/// GetJob message implementation.
+impl Message for GetJob {
+    /// The type of the result.
+    /// A result that contains either the job or an error.
+    type Response = Result<Job, Error>;
+}
+
+/// Handler for the GetJob message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetJob> for Git {
+    async fn handle(&mut self, msg: GetJob, _ctx: &mut ActorContext<Event>) -> Result<Job, Error> {
+        let request = self
+            .request(Method::GET, format!("{}/projects/{}/jobs/{}", self.base_url, msg.project_id, msg.job_id));
+        let res = self.send(request).await
+            .map_err(|err| Error::other(err.to_string()))?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Err(Error::new(ErrorKind::NotFound, format!("The job {} could not be found.", msg.job_id)));
+        }
+        let res = res.error_for_status().map_err(|err| Error::other(err.to_string()))?;
+        self.parse_json::<Job>(res).await
+    }
+}
+
+/// ---------- Probe Job ---------- ///
+/// Message used to check that a job can be read with a HEAD request, without modifying it. Reading a job does not
+/// prove that the token can erase it.
+#[derive(Clone)]
+pub struct ProbeJob {
+    /// The id of the project of the job.
+    pub project_id: u64,
+    /// The id of the job to probe.
+    pub job_id: u64
+}
+
+/// ProbeJob message implementation.
+impl Message for ProbeJob {
+    /// The type of the result.
+    /// A result that contains either nothing when the job can be read or an error.
+    type Response = Result<(), Error>;
+}
+
+/// Handler for the ProbeJob message for the Git actor.
+#[async_trait]
+impl Handler<Event, ProbeJob> for Git {
+    async fn handle(&mut self, msg: ProbeJob, _ctx: &mut ActorContext<Event>) -> Result<(), Error> {
+        let request = self
+            .request(Method::HEAD, format!("{}/projects/{}/jobs/{}", self.base_url, msg.project_id, msg.job_id));
+        let res = self.send(request).await
+            .map_err(|err| Error::other(err.to_string()))?;
+        if matches!(res.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND) {
+            return Err(Error::new(ErrorKind::PermissionDenied, format!(
+                "The token cannot read the job {} ({}).", msg.job_id, res.status())));
+        }
+        res.error_for_status().map_err(|err| Error::other(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// ---------- Erase Job ---------- ///
+/// Message used to erase a job from the Gitlab API.
+#[derive(Clone)]
+pub struct EraseJob {
+    /// The id of the project to erase the job from.
+    pub project_id: u64,
+    /// The id of the job to erase.
+    pub job_id: u64
+}
+
+/// Outcome of an erase request that did not fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EraseOutcome {
+    /// The item was erased.
+    Erased,
+    /// The item was already gone, e.g. erased by another process since it was listed.
+    AlreadyGone,
+    /// The erase was accepted and is processed asynchronously by the Gitlab instance.
+    Accepted,
+}
+
+/// EraseJob message implementation.
+impl Message for EraseJob {
+    /// The type of the result.
+    /// A result that contains either the outcome of the erase or an error.
+    type Response = Result<EraseOutcome, Error>;
+}
+
+/// Handler for the EraseJob message for the Git actor.
+#[async_trait]
+impl Handler<Event, EraseJob> for Git {
+    async fn handle(&mut self, msg: EraseJob, ctx: &mut ActorContext<Event>) -> Result<EraseOutcome, Error> {
+        let mut request = self
+            .request(Method::POST, format!("{}/projects/{}/jobs/{}/erase", self.base_url, msg.project_id, msg.job_id));
+        if let Some(timeout) = self.erase_timeout {
+            request = request.timeout(timeout);
+        }
+        let result = match self.send(request).await {
+            Ok(res) if res.status() == StatusCode::NOT_FOUND => Ok(EraseOutcome::AlreadyGone),
+            Ok(res) if res.status() == StatusCode::ACCEPTED => Ok(EraseOutcome::Accepted),
+            Ok(res) => check_write_status(res, "erase jobs").await
+                .map(|_| EraseOutcome::Erased),
+            Err(err) => Err(Error::other(err.to_string())),
+        };
+        ctx.system.publish(Event::from_erase("jobs", msg.project_id, msg.job_id, &result));
+        result
+    }
+}
+
+/// ---------- Delete Job Artifacts ---------- ///
+/// Message used to delete the artifacts of a job from the Gitlab API, keeping the job and its log.
+#[derive(Clone)]
+pub struct DeleteJobArtifacts {
+    /// The id of the project of the job.
+    pub project_id: u64,
+    /// The id of the job to delete the artifacts of.
+    pub job_id: u64
+}
+
+/// DeleteJobArtifacts message implementation.
+impl Message for DeleteJobArtifacts {
+    /// The type of the result.
+    /// A result that contains either the outcome of the deletion or an error.
+    type Response = Result<EraseOutcome, Error>;
+}
+
+/// Handler for the DeleteJobArtifacts message for the Git actor.
+#[async_trait]
+impl Handler<Event, DeleteJobArtifacts> for Git {
+    async fn handle(&mut self, msg: DeleteJobArtifacts, ctx: &mut ActorContext<Event>) -> Result<EraseOutcome, Error> {
+        let request = self
+            .request(Method::DELETE, format!("{}/projects/{}/jobs/{}/artifacts", self.base_url, msg.project_id, msg.job_id));
+        let result = match self.send(request).await {
+            Ok(res) if res.status() == StatusCode::NOT_FOUND => Ok(EraseOutcome::AlreadyGone),
+            Ok(res) => check_write_status(res, "delete artifacts").await
+                .map(|_| EraseOutcome::Erased),
+            Err(err) => Err(Error::other(err.to_string())),
+        };
+        ctx.system.publish(Event::from_erase("artifacts", msg.project_id, msg.job_id, &result));
+        result
+    }
+}
+
+/// ---------- Get Pipelines ---------- ///
+/// Message used to get a page of pipelines from the Gitlab API.
+#[derive(Clone)]
+pub struct GetPipelines {
+    /// The id of the project to get the pipelines from.
+    pub project_id: u64,
+    /// The date the pipelines must be created before, if any.
+    pub created_before: Option<DateTime<Utc>>,
+    /// The date the pipelines must be created after, if any.
+    pub created_after: Option<DateTime<Utc>>,
+    /// The statuses the pipelines must have, all statuses when empty.
+    pub statuses: Vec<String>,
+    /// The page of the pipelines to get.
+    pub page: u64
+}
+
+/// GetPipelinesResponse structure that holds the response of the GetPipelines message.
+pub struct GetPipelinesResponse {
+    /// The pipelines that were found.
+    pub pipelines: Vec<Pipeline>,
+    /// The next page of pipelines to get.
+    pub next_page: Option<u64>,
+    /// The creation date of the oldest pipeline of the page, before filtering.
+    pub oldest_created_at: Option<DateTime<Utc>>
+}
+
+/// GetPipelines message implementation.
+impl Message for GetPipelines {
+    /// The type of the result.
+    /// A result that contains either the pipelines that were found or an error.
+    type Response = Result<GetPipelinesResponse, Error>;
+}
+
+/// Handler for the GetPipelines message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetPipelines> for Git {
+    async fn handle(&mut self, msg: GetPipelines, ctx: &mut ActorContext<Event>) -> Result<GetPipelinesResponse, Error> {
+        let request = self
+            .request(Method::GET, format!("{}/projects/{}/pipelines", self.base_url, msg.project_id))
+            .query(&[("per_page", "50"), ("page", &msg.page.to_string())]);
+        let res = check_list_status(self.send(request).await?)?;
+        let next_page = res.headers()
+            .get("x-next-page")
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.parse::<u64>().ok());
+        let pipelines: Vec<Pipeline> = self.parse_json(res).await?;
+        let oldest_created_at = pipelines.iter().map(|pipeline| pipeline.created_at).min();
+        let pipelines_to_delete: Vec<Pipeline> = pipelines.into_iter()
+            .filter(|pipeline| msg.created_before.is_none_or(|date| pipeline.created_at < date))
+            .filter(|pipeline| msg.created_after.is_none_or(|date| pipeline.created_at > date))
+            .filter(|pipeline| msg.statuses.is_empty() || msg.statuses.contains(&pipeline.status))
+            .collect();
+        ctx.system.publish(Event::PageFetched { target: "pipelines", project_id: msg.project_id, page: msg.page, count: pipelines_to_delete.len() });
+        Ok(GetPipelinesResponse {
+            pipelines: pipelines_to_delete,
+            next_page,
+            oldest_created_at
+        })
+    }
+}
+
+/// ---------- Delete Pipeline ---------- ///
+/// Message used to delete a pipeline from the Gitlab API.
+#[derive(Clone)]
+pub struct DeletePipeline {
+    /// The id of the project to delete the pipeline from.
+    pub project_id: u64,
+    /// The id of the pipeline to delete.
+    pub pipeline_id: u64
+}
+
+/// DeletePipeline message implementation.
+impl Message for DeletePipeline {
+    /// The type of the result.
+    /// A result that contains either the outcome of the deletion or an error.
+    type Response = Result<EraseOutcome, Error>;
+}
+
+/// Handler for the DeletePipeline message for the Git actor.
+#[async_trait]
+impl Handler<Event, DeletePipeline> for Git {
+    async fn handle(&mut self, msg: DeletePipeline, ctx: &mut ActorContext<Event>) -> Result<EraseOutcome, Error> {
+        let request = self
+            .request(Method::DELETE, format!("{}/projects/{}/pipelines/{}", self.base_url, msg.project_id, msg.pipeline_id));
+        let result = match self.send(request).await {
+            Ok(res) => check_write_status(res, "delete pipelines").await
+                .map(|_| EraseOutcome::Erased),
+            Err(err) => Err(Error::other(err.to_string())),
+        };
+        ctx.system.publish(Event::from_erase("pipelines", msg.project_id, msg.pipeline_id, &result));
+        result
+    }
+}
+
+/// ---------- Get Deployments ---------- ///
+/// Message used to get a page of deployments of an environment from the Gitlab API.
+#[derive(Clone)]
+pub struct GetDeployments {
+    /// The id of the project to get the deployments from.
+    pub project_id: u64,
+    /// The name of the environment of the deployments.
+    pub environment: String,
+    /// The date the deployments must be created before, if any.
+    pub created_before: Option<DateTime<Utc>>,
+    /// The date the deployments must be created after, if any.
+    pub created_after: Option<DateTime<Utc>>,
+    /// The page of the deployments to get.
+    pub page: u64
+}
+
+/// GetDeploymentsResponse structure that holds the response of the GetDeployments message.
+pub struct GetDeploymentsResponse {
+    /// The deployments that were found.
+    pub deployments: Vec<Deployment>,
+    /// The next page of deployments to get.
+    pub next_page: Option<u64>
+}
+
+/// GetDeployments message implementation.
+impl Message for GetDeployments {
+    /// The type of the result.
+    /// A result that contains either the deployments that were found or an error.
+    type Response = Result<GetDeploymentsResponse, Error>;
+}
+
+/// Handler for the GetDeployments message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetDeployments> for Git {
+    async fn handle(&mut self, msg: GetDeployments, ctx: &mut ActorContext<Event>) -> Result<GetDeploymentsResponse, Error> {
+        let request = self
+            .request(Method::GET, format!("{}/projects/{}/deployments", self.base_url, msg.project_id))
+            .query(&[("per_page", "50"), ("page", &msg.page.to_string()), ("environment", &msg.environment)]);
+        let res = check_list_status(self.send(request).await?)?;
+        let next_page = res.headers()
+            .get("x-next-page")
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.parse::<u64>().ok());
+        let deployments: Vec<Deployment> = self.parse_json(res).await?;
+        let deployments_to_delete: Vec<Deployment> = deployments.into_iter()
+            .filter(|deployment| msg.created_before.is_none_or(|date| deployment.created_at < date))
+            .filter(|deployment| msg.created_after.is_none_or(|date| deployment.created_at > date))
+            .collect();
+        ctx.system.publish(Event::PageFetched { target: "deployments", project_id: msg.project_id, page: msg.page, count: deployments_to_delete.len() });
+        Ok(GetDeploymentsResponse {
+            deployments: deployments_to_delete,
+            next_page
+        })
+    }
+}
+
+/// ---------- Delete Deployment ---------- ///
+/// Message used to delete a deployment from the Gitlab API.
+#[derive(Clone)]
+pub struct DeleteDeployment {
+    /// The id of the project to delete the deployment from.
+    pub project_id: u64,
+    /// The id of the deployment to delete.
+    pub deployment_id: u64
+}
+
+/// DeleteDeployment message implementation.
+impl Message for DeleteDeployment {
+    /// The type of the result.
+    /// A result that contains either the outcome of the deletion or an error.
+    type Response = Result<EraseOutcome, Error>;
+}
+
+/// Handler for the DeleteDeployment message for the Git actor.
+#[async_trait]
+impl Handler<Event, DeleteDeployment> for Git {
+    async fn handle(&mut self, msg: DeleteDeployment, ctx: &mut ActorContext<Event>) -> Result<EraseOutcome, Error> {
+        let request = self
+            .request(Method::DELETE, format!("{}/projects/{}/deployments/{}", self.base_url, msg.project_id, msg.deployment_id));
+        let result = match self.send(request).await {
+            Ok(res) => check_write_status(res, "delete deployments").await
+                .map(|_| EraseOutcome::Erased),
+            Err(err) => Err(Error::other(err.to_string())),
+        };
+        ctx.system.publish(Event::from_erase("deployments", msg.project_id, msg.deployment_id, &result));
+        result
+    }
+}
+
+/// ---------- Get Packages ---------- ///
+/// Message used to get a page of packages from the Gitlab API.
+/// Each package is a single version of a package name.
+#[derive(Clone)]
+pub struct GetPackages {
+    /// The id of the project to get the packages from.
+    pub project_id: u64,
+    /// The page of the packages to get.
+    pub page: u64
+}
+
+/// GetPackagesResponse structure that holds the response of the GetPackages message.
+pub struct GetPackagesResponse {
+    /// The packages that were found, unfiltered since the retention of the latest versions
+    /// needs all the versions of a package.
+    pub packages: Vec<Package>,
+    /// The next page of packages to get.
+    pub next_page: Option<u64>
+}
+
+/// GetPackages message implementation.
+impl Message for GetPackages {
+    /// The type of the result.
+    /// A result that contains either the packages that were found or an error.
+    type Response = Result<GetPackagesResponse, Error>;
+}
+
+/// Handler for the GetPackages message for the Git actor.
+#[async_trait]
+impl Handler<Event, GetPackages> for Git {
+    async fn handle(&mut self, msg: GetPackages, ctx: &mut ActorContext<Event>) -> Result<GetPackagesResponse, Error> {
+        let request = self
+            .request(Method::GET, format!("{}/projects/{}/packages", self.base_url, msg.project_id))
+            .query(&[("per_page", "50"), ("page", &msg.page.to_string())]);
+        let res = check_list_status(self.send(request).await?)?;
+        let next_page = res.headers()
+            .get("x-next-page")
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.parse::<u64>().ok());
+        let packages: Vec<Package> = self.parse_json(res).await?;
+        ctx.system.publish(Event::PageFetched { target: "packages", project_id: msg.project_id, page: msg.page, count: packages.len() });
+        Ok(GetPackagesResponse {
+            packages,
+            next_page
+        })
+    }
+}
+
+/// ---------- Delete Package ---------- ///
+/// Message used to delete a package version from the Gitlab API.
+#[derive(Clone)]
+pub struct DeletePackage {
+    /// The id of the project to delete the package from.
+    pub project_id: u64,
+    /// The id of the package to delete.
+    pub package_id: u64
+}
+
+/// DeletePackage message implementation.
+impl Message for DeletePackage {
+    /// The type of the result.
+    /// A result that contains either the outcome of the deletion or an error.
+    type Response = Result<EraseOutcome, Error>;
+}
+
+/// Handler for the DeletePackage message for the Git actor.
+#[async_trait]
+impl Handler<Event, DeletePackage> for Git {
+    async fn handle(&mut self, msg: DeletePackage, ctx: &mut ActorContext<Event>) -> Result<EraseOutcome, Error> {
+        let request = self
+            .request(Method::DELETE, format!("{}/projects/{}/packages/{}", self.base_url, msg.project_id, msg.package_id));
+        let result = match self.send(request).await {
+            Ok(res) => check_write_status(res, "delete packages").await
+                .map(|_| EraseOutcome::Erased),
+            Err(err) => Err(Error::other(err.to_string())),
+        };
+        ctx.system.publish(Event::from_erase("packages", msg.project_id, msg.package_id, &result));
+        result
+    }
+}
+
+/// ---------------------------- ///
+/// ---------- Models ---------- ///
+/// ---------------------------- ///
+///
+/// Common interface of the resources that can be cleaned.
+pub trait Resource {
+    /// The id of the resource.
+    fn id(&self) -> u64;
+    /// The creation date of the resource.
+    fn created_at(&self) -> DateTime<Utc>;
+    /// The git ref the resource was created for, if any.
+    fn git_ref(&self) -> Option<&str> { None }
+    /// The status of the resource, if any.
+    fn status(&self) -> Option<&str> { None }
+    /// The storage in bytes reclaimed by erasing the resource, if known.
+    fn size(&self) -> Option<u64> { None }
+}
+
+/// Project model.
+/// The models accept the camelCase names and a few alternates of the fields, as renamed by some proxies and
+/// instances, and only require the fields they cannot do without.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Project {
+    /// The id of the project.
+    pub id: u64,
     /// The name of the project.
     pub name: String,
+    /// The full path of the project, including its groups, e.g. `group/subgroup/project`.
+    #[serde(default, alias = "pathWithNamespace", alias = "full_path")]
+    pub path_with_namespace: String,
+    /// The visibility of the project: `public`, `internal` or `private`.
+    #[serde(default)]
+    pub visibility: String,
+    /// Whether the project is archived.
+    #[serde(default)]
+    pub archived: bool,
+    /// The date of the last activity on the project, if known.
+    #[serde(default, alias = "lastActivityAt")]
+    pub last_activity_at: Option<DateTime<Utc>>,
+}
+
+/// User model.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct User {
+    /// The id of the user.
+    pub id: u64,
+    /// The username of the user.
+    pub username: String,
+}
+
+/// Gitlab version model.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GitlabVersion {
+    /// The version of the instance, e.g. `16.5.1-ee`.
+    pub version: String,
+    /// The revision of the instance.
+    pub revision: String,
+}
+
+impl GitlabVersion {
+    /// The major and minor numbers of the version, if it can be parsed.
+    pub fn major_minor(&self) -> Option<(u64, u64)> {
+        let mut numbers = self.version.split(['.', '-']);
+        let major = numbers.next()?.parse().ok()?;
+        let minor = numbers.next()?.parse().ok()?;
+        Some((major, minor))
+    }
+
+    /// Whether the version is at least the given major and minor version.
+    /// An unparsable version is considered recent enough.
+    pub fn is_at_least(&self, minimum: (u64, u64)) -> bool {
+        self.major_minor().is_none_or(|version| version >= minimum)
+    }
 }
 
 /// Job model.
@@ -204,8 +1485,701 @@ pub struct Job {
     /// The id of the job.
     pub id: u64,
     /// The creation date of the job.
+    #[serde(alias = "createdAt")]
     pub created_at: DateTime<Utc>,
     /// The erase date of the job.
-    pub erased_at: Option<DateTime<Utc>>
+    #[serde(default, alias = "erasedAt")]
+    pub erased_at: Option<DateTime<Utc>>,
+    /// The expiration date of the artifacts of the job, if any.
+    #[serde(default, alias = "artifactsExpireAt")]
+    pub artifacts_expire_at: Option<DateTime<Utc>>,
+    /// The status of the job, e.g. `success` or `failed`.
+    #[serde(default)]
+    pub status: String,
+    /// The date the job finished, if it did.
+    #[serde(default, alias = "finishedAt")]
+    pub finished_at: Option<DateTime<Utc>>,
+    /// The artifacts of the job, including its log (`trace`).
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    /// The name of the job.
+    #[serde(default)]
+    pub name: String,
+    /// The branch or tag the job ran for.
+    #[serde(default, rename = "ref", alias = "git_ref")]
+    pub git_ref: String,
+    /// The pipeline the job belongs to, if known.
+    #[serde(default)]
+    pub pipeline: Option<PipelineRef>
+}
+
+/// Pipeline reference model, the pipeline of a job as nested in the job.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PipelineRef {
+    /// The id of the pipeline.
+    pub id: u64,
+    /// The status of the pipeline, e.g. `success` or `failed`.
+    #[serde(default)]
+    pub status: String,
+}
+
+/// Artifact model.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Artifact {
+    /// The type of the artifact, e.g. `archive`, `metadata`, `junit` or `trace`.
+    #[serde(alias = "fileType")]
+    pub file_type: String,
+    /// The size of the artifact in bytes.
+    #[serde(default, alias = "file_size")]
+    pub size: u64,
+}
+
+/// The date of a job the date window applies to.
+#[derive(Clone, Debug, Default, PartialEq, ValueEnum)]
+pub enum AgeField {
+    /// The creation date of the job.
+    #[default]
+    Created,
+    /// The date the job finished, or its creation date when it did not finish.
+    Finished,
+}
+
+impl Job {
+    /// The date of the job the date window applies to, falling back to the creation date for an unfinished job.
+    pub fn age_date(&self, field: &AgeField) -> DateTime<Utc> {
+        match field {
+            AgeField::Created => self.created_at,
+            AgeField::Finished => self.finished_at.unwrap_or(self.created_at),
+        }
+    }
+
+    /// The total size in bytes of the artifacts of the job, including its log.
+    pub fn artifacts_size(&self) -> u64 {
+        self.artifacts.iter().map(|artifact| artifact.size).sum()
+    }
+}
+
+/// Pipeline model.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pipeline {
+    /// The id of the pipeline.
+    pub id: u64,
+    /// The status of the pipeline, e.g. `success` or `failed`.
+    pub status: String,
+    /// The creation date of the pipeline.
+    pub created_at: DateTime<Utc>,
+    /// The git ref the pipeline ran for, e.g. a branch or a tag.
+    #[serde(default, rename = "ref")]
+    pub git_ref: String
+}
+
+/// Deployment model.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Deployment {
+    /// The id of the deployment.
+    pub id: u64,
+    /// The creation date of the deployment.
+    pub created_at: DateTime<Utc>,
+    /// The environment of the deployment.
+    pub environment: Environment
 }
 
+/// Environment model.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Environment {
+    /// The id of the environment.
+    pub id: u64,
+    /// The name of the environment.
+    pub name: String
+}
+
+/// Package model, a single version of a package.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Package {
+    /// The id of the package.
+    pub id: u64,
+    /// The name of the package.
+    pub name: String,
+    /// The version of the package.
+    pub version: String,
+    /// The type of the package, e.g. `npm` or `maven`.
+    pub package_type: String,
+    /// The creation date of the package.
+    pub created_at: DateTime<Utc>
+}
+
+impl Resource for Job {
+    fn id(&self) -> u64 { self.id }
+    fn created_at(&self) -> DateTime<Utc> { self.created_at }
+    fn git_ref(&self) -> Option<&str> { Some(&self.git_ref) }
+    fn status(&self) -> Option<&str> { Some(&self.status) }
+    fn size(&self) -> Option<u64> { Some(self.artifacts_size()) }
+}
+
+impl Resource for Pipeline {
+    fn id(&self) -> u64 { self.id }
+    fn created_at(&self) -> DateTime<Utc> { self.created_at }
+    fn git_ref(&self) -> Option<&str> { Some(&self.git_ref) }
+    fn status(&self) -> Option<&str> { Some(&self.status) }
+}
+
+impl Resource for Deployment {
+    fn id(&self) -> u64 { self.id }
+    fn created_at(&self) -> DateTime<Utc> { self.created_at }
+}
+
+impl Resource for Package {
+    fn id(&self) -> u64 { self.id }
+    fn created_at(&self) -> DateTime<Utc> { self.created_at }
+}
+
+#[cfg(test)]
+mod tests {
+    use tiny_tokio_actor::{ActorPath, ActorSystem, EventBus};
+
+    use super::*;
+    use crate::clock::{FixedClock, MockClock};
+    use crate::mock::{MockResponse, MockServer};
+
+    /// The context of the actor when its handlers are called directly.
+    fn context() -> ActorContext<Event> {
+        ActorContext { path: ActorPath::from("/user/git-actor"), system: ActorSystem::new("test", EventBus::new(100)) }
+    }
+
+    /// A Git actor sending its requests to the mock instance, timed with the clock.
+    fn git(server: &MockServer, clock: Arc<dyn Clock>, config: GitConfig) -> Git {
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        Git::new(GitConfig {
+            base_url: server.url.clone(),
+            token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+            clock: Some(clock),
+            ..config
+        }).unwrap()
+    }
+
+    fn date(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    /// The message getting a page of the jobs of the project 1 created in the window.
+    fn get_jobs(page: u64, created_before: Option<DateTime<Utc>>, created_after: Option<DateTime<Utc>>) -> GetJobs {
+        GetJobs { project_id: 1, created_before, created_after, age_field: AgeField::Created, keyset: false, after_id: None, page, per_page: 100 }
+    }
+
+    #[test]
+    fn expand_env_replaces_the_references() {
+        std::env::set_var("GITLAB_CLEANER_TEST_HOST", "gitlab.example.com");
+        assert_eq!(expand_env("https://${GITLAB_CLEANER_TEST_HOST}/api").unwrap(), "https://gitlab.example.com/api");
+        assert_eq!(expand_env("no reference").unwrap(), "no reference");
+        assert_eq!(expand_env("${GITLAB_CLEANER_TEST_UNDEFINED}").unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(expand_env("${GITLAB_CLEANER_TEST_HOST").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn requests_are_sent_to_the_expanded_url_with_the_expanded_headers() {
+        let server = MockServer::start(|_| MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project" }))).await;
+        std::env::set_var("GITLAB_CLEANER_TEST_URL", &server.url);
+        std::env::set_var("GITLAB_CLEANER_TEST_TRACE", "on");
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        let mut git = Git::new(GitConfig {
+            base_url: "${GITLAB_CLEANER_TEST_URL}".to_string(),
+            headers: vec![("X-Trace".to_string(), "${GITLAB_CLEANER_TEST_TRACE}".to_string())],
+            token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+            ..GitConfig::default()
+        }).unwrap();
+        git.handle(GetProjectById { project_id: 1 }, &mut context()).await.unwrap();
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[0].path, "/api/v4/projects/1");
+        assert_eq!(requests[0].header("X-Trace"), Some("on"));
+        assert_eq!(requests[0].header("PRIVATE-TOKEN"), Some("token"));
+    }
+
+    #[test]
+    fn malformed_urls_are_rejected_at_construction() {
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        for base_url in ["", "gitlab.example.com", "ftp://gitlab.example.com"] {
+            let err = Git::new(GitConfig {
+                base_url: base_url.to_string(),
+                token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+                ..GitConfig::default()
+            }).err().unwrap();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+            assert!(err.to_string().contains("must be an absolute http(s) url"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn missing_or_empty_tokens_are_rejected_at_construction() {
+        let config = |token_env: &str| GitConfig {
+            base_url: "https://gitlab.example.com".to_string(),
+            token_env: Some(token_env.to_string()),
+            ..GitConfig::default()
+        };
+        let err = Git::new(config("GITLAB_CLEANER_TEST_UNSET_TOKEN")).err().unwrap();
+        assert_eq!((err.kind(), err.to_string()), (ErrorKind::NotFound, "The GITLAB_CLEANER_TEST_UNSET_TOKEN environment variable is not set.".to_string()));
+        std::env::set_var("GITLAB_CLEANER_TEST_BLANK_TOKEN", "  \n");
+        let err = Git::new(config("GITLAB_CLEANER_TEST_BLANK_TOKEN")).err().unwrap();
+        assert_eq!((err.kind(), err.to_string()), (ErrorKind::InvalidInput, "The GITLAB_CLEANER_TEST_BLANK_TOKEN environment variable is empty.".to_string()));
+    }
+
+    #[tokio::test]
+    async fn connect_retries_wait_on_the_clock() {
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let mut git = Git::new(GitConfig {
+            // Nothing listens on the port 1, so that each attempt fails to connect.
+            base_url: "http://127.0.0.1:1".to_string(),
+            connect_retries: 3,
+            backoff: Backoff { max_delay: Some(Duration::from_millis(500)), max_total_wait: None },
+            token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+            clock: Some(clock.clone()),
+            ..GitConfig::default()
+        }).unwrap();
+        let request = git.request(Method::GET, format!("{}/version", git.base_url));
+        assert!(git.send(request).await.is_err());
+        assert_eq!(*clock.sleeps.lock().unwrap(), vec![Duration::from_millis(400), Duration::from_millis(500), Duration::from_millis(500)]);
+        assert_eq!(git.metrics.lock().unwrap().count(), 4);
+    }
+
+    #[tokio::test]
+    async fn pause_follows_the_rate_limit_reset_with_a_fixed_clock() {
+        // The dates of the instance and the pretended date are years apart, only the reset relative to the response counts.
+        let server = MockServer::start(|_| MockResponse::json(200, serde_json::json!({ "version": "16.5.0", "revision": "abc" }))
+            .header("Date", "Tue, 01 Jan 2030 00:00:00 GMT")
+            .header("RateLimit-Remaining", 3)
+            .header("RateLimit-Reset", DateTime::parse_from_rfc3339("2030-01-01T00:00:02Z").unwrap().timestamp())).await;
+        let clock = Arc::new(FixedClock(DateTime::parse_from_rfc3339("2020-06-01T00:00:00Z").unwrap().with_timezone(&Utc)));
+        let mut git = git(&server, clock, GitConfig { rate_limit_threshold: Some(5), ..GitConfig::default() });
+        let before = Instant::now();
+        git.handle(GetVersion, &mut context()).await.unwrap();
+        let until = git.handle(GetPause, &mut context()).await.unwrap();
+        assert!(until > before + Duration::from_secs(1) && until <= Instant::now() + Duration::from_secs(2));
+        assert_eq!(server.paths("GET"), vec!["/api/v4/version"]);
+    }
+
+    #[tokio::test]
+    async fn erase_pauses_when_the_rate_limit_falls_to_the_threshold() {
+        let remaining = Arc::new(std::sync::atomic::AtomicU64::new(10));
+        let served = remaining.clone();
+        let server = MockServer::start(move |_| MockResponse::empty(204)
+            .header("RateLimit-Remaining", served.fetch_sub(5, std::sync::atomic::Ordering::SeqCst))
+            .header("RateLimit-Reset", Utc::now().timestamp() + 30)).await;
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let mut git = git(&server, clock.clone(), GitConfig { rate_limit_threshold: Some(5), ..GitConfig::default() });
+        let erase = || EraseJob { project_id: 1, job_id: 2 };
+
+        assert_eq!(git.handle(erase(), &mut context()).await.unwrap(), EraseOutcome::Erased);
+        assert_eq!(git.handle(GetPause, &mut context()).await, None);
+        git.handle(erase(), &mut context()).await.unwrap();
+        let until = git.handle(GetPause, &mut context()).await.unwrap();
+        assert!(until > clock.instant() + Duration::from_secs(28) && until <= clock.instant() + Duration::from_secs(30));
+
+        // The pause is over once the clock waited until the reset.
+        clock.sleep(Duration::from_secs(30)).await;
+        assert_eq!(git.handle(GetPause, &mut context()).await, None);
+    }
+
+    #[test]
+    fn project_cache_entries_expire_after_the_ttl() {
+        let start = Instant::now();
+        let group = Some("group".to_string());
+        let mut cache = ProjectCache::new(Some(Duration::from_secs(60)));
+        cache.insert("project", &group, 42, start);
+        assert_eq!(cache.get("project", &group, start + Duration::from_secs(59)), Some(42));
+        assert_eq!(cache.get("project", &group, start + Duration::from_secs(60)), None);
+        assert_eq!(cache.get("project", &None, start), None);
+        let mut forever = ProjectCache::new(None);
+        forever.insert("project", &None, 7, start);
+        assert_eq!(forever.get("project", &None, start + Duration::from_secs(86400)), Some(7));
+    }
+
+    #[tokio::test]
+    async fn get_project_is_resolved_again_once_expired_on_the_clock() {
+        let server = MockServer::start(|_| MockResponse::json(200, serde_json::json!([{ "id": 42, "name": "project" }]))).await;
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let mut git = git(&server, clock.clone(), GitConfig { project_cache_ttl: Some(Duration::from_secs(60)), ..GitConfig::default() });
+        let get = || GetProject { project_name: "project".to_string(), project_group: None };
+        assert_eq!(git.handle(get(), &mut context()).await.unwrap(), 42);
+        clock.sleep(Duration::from_secs(59)).await;
+        assert_eq!(git.handle(get(), &mut context()).await.unwrap(), 42);
+        assert_eq!(server.paths("GET").len(), 1);
+        clock.sleep(Duration::from_secs(1)).await;
+        assert_eq!(git.handle(get(), &mut context()).await.unwrap(), 42);
+        assert_eq!(server.paths("GET"), vec!["/api/v4/projects?search=project&scope=projects"; 2]);
+    }
+
+    #[tokio::test]
+    async fn search_projects_reports_the_cause_of_a_failure() {
+        let server = MockServer::start(|_| MockResponse::json(401, serde_json::json!({ "message": "401 Unauthorized" }))).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let err = git.search_projects("project", None, None).await.unwrap_err();
+        assert!(err.to_string().contains("401 Unauthorized"), "{}", err);
+
+        let mut unreachable = git.clone();
+        unreachable.base_url = "http://127.0.0.1:1/api/v4".to_string();
+        let err = unreachable.search_projects("project", None, None).await.unwrap_err();
+        assert!(err.to_string().starts_with("Search request failed: "), "{}", err);
+        assert!(err.to_string().len() > "Search request failed: ".len());
+    }
+
+    #[tokio::test]
+    async fn erase_names_the_scope_missing_from_the_token() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/api/v4/projects/1/jobs/2/erase" => MockResponse::json(403, serde_json::json!({ "error": "insufficient_scope", "scope": "api" })),
+            _ => MockResponse::json(403, serde_json::json!({ "message": "403 Forbidden" })),
+        }).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let err = git.handle(EraseJob { project_id: 1, job_id: 2 }, &mut context()).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(err.to_string().starts_with("Your token needs the `api` scope to erase jobs"), "{}", err);
+        let err = git.handle(DeletePipeline { project_id: 1, pipeline_id: 3 }, &mut context()).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("(403 Forbidden)"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn probe_job_only_checks_the_read_access() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/api/v4/projects/1/jobs/2" => MockResponse::json(200, serde_json::json!({ "id": 2 })),
+            _ => MockResponse::json(403, serde_json::json!({ "message": "403 Forbidden" })),
+        }).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        git.handle(ProbeJob { project_id: 1, job_id: 2 }, &mut context()).await.unwrap();
+        let err = git.handle(ProbeJob { project_id: 1, job_id: 3 }, &mut context()).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(err.to_string(), "The token cannot read the job 3 (403 Forbidden).");
+        assert_eq!(server.paths("HEAD"), vec!["/api/v4/projects/1/jobs/2", "/api/v4/projects/1/jobs/3"]);
+    }
+
+    #[tokio::test]
+    async fn get_jobs_keeps_the_jobs_created_in_the_window() {
+        let server = MockServer::start(|_| MockResponse::json(200, serde_json::json!([
+            { "id": 4, "created_at": "2024-04-01T00:00:00Z", "status": "failed", "ref": "main" },
+            { "id": 3, "created_at": "2024-03-01T00:00:00Z", "status": "failed", "ref": "main" },
+            { "id": 2, "created_at": "2024-02-01T00:00:00Z", "status": "failed", "ref": "main", "erased_at": "2024-02-02T00:00:00Z" },
+            { "id": 1, "created_at": "2024-01-01T00:00:00Z", "status": "failed", "ref": "main" },
+        ]))).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let window = get_jobs(1, Some(date("2024-04-01T00:00:00Z")), Some(date("2024-01-01T00:00:00Z")));
+        let response = git.handle(window, &mut context()).await.unwrap();
+        assert_eq!(response.jobs.iter().map(|job| job.id).collect::<Vec<_>>(), vec![3]);
+        assert_eq!((response.listed, response.oldest_created_at), (4, Some(date("2024-01-01T00:00:00Z"))));
+    }
+
+    #[tokio::test]
+    async fn get_job_fetches_a_single_job() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/api/v4/projects/1/jobs/5" => MockResponse::json(200, serde_json::json!({
+                "id": 5, "created_at": "2024-01-01T00:00:00Z", "status": "failed", "ref": "main", "erased_at": "2024-01-02T00:00:00Z"
+            })),
+            "/api/v4/projects/1/jobs/6" => MockResponse::json(404, serde_json::json!({ "message": "404 Not found" })),
+            _ => MockResponse::json(500, serde_json::json!({ "message": "500 Internal Server Error" })),
+        }).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let job = git.handle(GetJob { project_id: 1, job_id: 5 }, &mut context()).await.unwrap();
+        assert_eq!((job.id, job.erased_at), (5, Some(date("2024-01-02T00:00:00Z"))));
+        let err = git.handle(GetJob { project_id: 1, job_id: 6 }, &mut context()).await.unwrap_err();
+        assert_eq!((err.kind(), err.to_string()), (ErrorKind::NotFound, "The job 6 could not be found.".to_string()));
+        let err = git.handle(GetJob { project_id: 1, job_id: 7 }, &mut context()).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn is_at_least_compares_the_major_and_minor() {
+        let version = |version: &str| GitlabVersion { version: version.to_string(), revision: String::new() };
+        assert!(version("16.5.1-ee").is_at_least((16, 5)));
+        assert!(version("17.0.0").is_at_least((16, 5)));
+        assert!(!version("16.4.9").is_at_least((16, 5)));
+        assert!(version("unknown").is_at_least((16, 5)));
+    }
+
+    #[test]
+    fn curl_command_redacts_the_token() {
+        let request = reqwest::Client::new()
+            .request(Method::DELETE, "https://host/api/v4/projects/1/jobs/2")
+            .header("PRIVATE-TOKEN", "secret")
+            .header("X-Trace", "on")
+            .build()
+            .unwrap();
+        let command = curl_command(&request);
+        assert!(command.starts_with("curl -X DELETE 'https://host/api/v4/projects/1/jobs/2'"));
+        assert!(command.contains("-H 'private-token: [REDACTED]'"));
+        assert!(command.contains("-H 'x-trace: on'"));
+        assert!(!command.contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn erasing_a_job_already_gone_is_skipped() {
+        let server = MockServer::start(|_| MockResponse::json(404, serde_json::json!({ "message": "404 Job Not Found" }))).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let mut ctx = context();
+        let mut events = ctx.system.events();
+        assert_eq!(git.handle(EraseJob { project_id: 1, job_id: 2 }, &mut ctx).await.unwrap(), EraseOutcome::AlreadyGone);
+        assert!(matches!(events.recv().await.unwrap(), Event::Skipped { target: "jobs", project_id: 1, id: 2 }));
+    }
+
+    #[tokio::test]
+    async fn deployments_are_listed_for_the_environment_and_deleted() {
+        let server = MockServer::start(|request| match request.method.as_str() {
+            "GET" => MockResponse::json(200, serde_json::json!([
+                { "id": 2, "created_at": "2024-02-01T00:00:00Z", "environment": { "id": 7, "name": "review" } },
+                { "id": 1, "created_at": "2024-01-01T00:00:00Z", "environment": { "id": 7, "name": "review" } },
+            ])).header("X-Next-Page", 2),
+            _ if request.path.ends_with("/deployments/1") => MockResponse::empty(204),
+            _ => MockResponse::json(403, serde_json::json!({ "message": "403 Forbidden" })),
+        }).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let response = git.handle(GetDeployments {
+            project_id: 1,
+            environment: "review".to_string(),
+            created_before: Some(date("2024-01-15T00:00:00Z")),
+            created_after: None,
+            page: 1,
+        }, &mut context()).await.unwrap();
+        assert_eq!(response.deployments.iter().map(|deployment| deployment.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(response.next_page, Some(2));
+        let listing = server.requests.lock().unwrap()[0].clone();
+        assert_eq!((listing.query("environment"), listing.query("page")), (Some("review"), Some("1")));
+
+        assert_eq!(git.handle(DeleteDeployment { project_id: 1, deployment_id: 1 }, &mut context()).await.unwrap(), EraseOutcome::Erased);
+        assert!(git.handle(DeleteDeployment { project_id: 1, deployment_id: 2 }, &mut context()).await.is_err());
+        assert_eq!(server.paths("DELETE"), vec!["/api/v4/projects/1/deployments/1", "/api/v4/projects/1/deployments/2"]);
+    }
+
+    #[test]
+    fn percentile_uses_the_nearest_rank() {
+        let mut metrics = RequestMetrics::default();
+        assert_eq!(metrics.percentile(0.95), Duration::ZERO);
+        for millis in [50, 10, 40, 20, 30] {
+            metrics.record(Duration::from_millis(millis));
+        }
+        assert_eq!(metrics.percentile(0.5), Duration::from_millis(30));
+        assert_eq!(metrics.percentile(0.95), Duration::from_millis(50));
+        assert_eq!(metrics.percentile(0.0), Duration::from_millis(10));
+        assert_eq!(metrics.average(), Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn an_html_page_points_at_the_web_ui() {
+        let server = MockServer::start(|_| MockResponse {
+            body: b"<!DOCTYPE html><html><body>Sign in</body></html>".to_vec(),
+            ..MockResponse::empty(200).header("Content-Type", "text/html; charset=utf-8")
+        }).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let err = git.handle(get_jobs(1, None, None), &mut context()).await.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("it looks like you pointed at the web UI, not the API"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn packages_are_listed_and_deleted() {
+        let server = MockServer::start(|request| match request.method.as_str() {
+            "GET" => MockResponse::json(200, serde_json::json!([
+                { "id": 3, "name": "app", "version": "1.1.0", "package_type": "npm", "created_at": "2024-02-01T00:00:00Z" },
+                { "id": 2, "name": "app", "version": "1.0.0", "package_type": "npm", "created_at": "2024-01-01T00:00:00Z" },
+            ])),
+            _ => MockResponse::empty(204),
+        }).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let response = git.handle(GetPackages { project_id: 1, page: 1 }, &mut context()).await.unwrap();
+        let versions: Vec<_> = response.packages.iter().map(|package| (package.id, package.version.as_str())).collect();
+        assert_eq!(versions, vec![(3, "1.1.0"), (2, "1.0.0")]);
+        assert_eq!(response.next_page, None);
+
+        assert_eq!(git.handle(DeletePackage { project_id: 1, package_id: 2 }, &mut context()).await.unwrap(), EraseOutcome::Erased);
+        assert_eq!(server.paths("DELETE"), vec!["/api/v4/projects/1/packages/2"]);
+    }
+
+    #[test]
+    fn api_url_appends_the_api_path_once() {
+        assert_eq!(api_url("https://host", None), "https://host/api/v4");
+        assert_eq!(api_url("https://host/gitlab/api/v4", None), "https://host/gitlab/api/v4");
+        assert_eq!(api_url("https://host", Some("/api/v5/")), "https://host/api/v5");
+        assert_eq!(api_url("https://host", Some("")), "https://host");
+    }
+
+    #[tokio::test]
+    async fn a_trailing_slash_does_not_double_the_slashes() {
+        let server = MockServer::start(|_| MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project" }))).await;
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        let mut git = Git::new(GitConfig {
+            base_url: format!("{}/", server.url),
+            token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+            ..GitConfig::default()
+        }).unwrap();
+        git.handle(GetProjectById { project_id: 1 }, &mut context()).await.unwrap();
+        assert_eq!(server.paths("GET"), vec!["/api/v4/projects/1"]);
+    }
+
+    #[tokio::test]
+    async fn jobs_are_counted_by_pages_without_total() {
+        let server = MockServer::start(|request| {
+            let page: u64 = request.query("page").unwrap().parse().unwrap();
+            let jobs: Vec<_> = (0..if page < 3 { 2 } else { 1 })
+                .map(|id| serde_json::json!({ "id": page * 10 + id, "created_at": "2024-01-01T00:00:00Z", "status": "failed", "ref": "main" }))
+                .collect();
+            let response = MockResponse::json(200, serde_json::json!(jobs));
+            if page < 3 { response.header("X-Next-Page", page + 1) } else { response }
+        }).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        assert_eq!(git.handle(CountJobs { project_id: 1 }, &mut context()).await.unwrap(), 5);
+        assert_eq!(server.paths("GET").len(), 3);
+
+        let counted = MockServer::start(|_| MockResponse::json(200, serde_json::json!([])).header("X-Total", 1234)).await;
+        let mut git = self::git(&counted, Arc::new(SystemClock), GitConfig::default());
+        assert_eq!(git.handle(CountJobs { project_id: 1 }, &mut context()).await.unwrap(), 1234);
+        assert_eq!(counted.paths("GET").len(), 1);
+    }
+
+    #[test]
+    fn backoff_caps_the_delay_and_the_total_wait() {
+        let unbounded = Backoff::default();
+        assert_eq!(unbounded.next(Duration::from_secs(8), Duration::from_secs(100)), Some(Duration::from_secs(8)));
+        let backoff = Backoff { max_delay: Some(Duration::from_secs(2)), max_total_wait: Some(Duration::from_secs(5)) };
+        assert_eq!(backoff.next(Duration::from_secs(8), Duration::ZERO), Some(Duration::from_secs(2)));
+        assert_eq!(backoff.next(Duration::from_secs(8), Duration::from_secs(3)), Some(Duration::from_secs(2)));
+        assert_eq!(backoff.next(Duration::from_secs(8), Duration::from_secs(4)), None);
+        assert_eq!(backoff.timeout_error(Duration::from_secs(4), &"refused").kind(), ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn connect_retries_stop_once_the_total_wait_is_exceeded() {
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let mut git = Git::new(GitConfig {
+            base_url: "http://127.0.0.1:1".to_string(),
+            connect_retries: 10,
+            backoff: Backoff { max_delay: Some(Duration::from_millis(500)), max_total_wait: Some(Duration::from_secs(1)) },
+            token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+            clock: Some(clock.clone()),
+            ..GitConfig::default()
+        }).unwrap();
+        let request = git.request(Method::GET, format!("{}/version", git.base_url));
+        let err = git.send(request).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(err.to_string().starts_with("Gave up retrying after waiting 0s in total (--max-total-wait-secs)"), "{}", err);
+        assert_eq!(*clock.sleeps.lock().unwrap(), vec![Duration::from_millis(400), Duration::from_millis(500)]);
+    }
+
+    #[tokio::test]
+    async fn a_missing_project_id_is_reported_clearly() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/api/v4/projects/1" => MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project" })),
+            _ => MockResponse::json(404, serde_json::json!({ "message": "404 Project Not Found" })),
+        }).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        assert_eq!(git.handle(GetProjectById { project_id: 1 }, &mut context()).await.unwrap().name, "project");
+        let err = git.handle(GetProjectById { project_id: 9 }, &mut context()).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "No project with the id 9 exists, or the token cannot access it.");
+    }
+
+    #[test]
+    fn body_excerpt_is_truncated_and_sanitized() {
+        assert_eq!(body_excerpt(b"Bad\x1b[31m request"), "Bad\u{FFFD}[31m request");
+        assert_eq!(body_excerpt(&[0xff, b'a']), "\u{FFFD}a");
+        let excerpt = body_excerpt("x".repeat(BODY_EXCERPT_LEN + 1).as_bytes());
+        assert_eq!(excerpt, format!("{}...", "x".repeat(BODY_EXCERPT_LEN)));
+    }
+
+    #[tokio::test]
+    async fn binary_error_bodies_are_quoted_lossily() {
+        let server = MockServer::start(|request| MockResponse {
+            body: vec![0xff, 0xfe, b'o', b'k', 0x00],
+            ..MockResponse::empty(if request.method == "POST" { 403 } else { 200 })
+        }).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let err = git.handle(EraseJob { project_id: 1, job_id: 2 }, &mut context()).await.unwrap_err();
+        assert!(err.to_string().ends_with("(403 Forbidden): \u{FFFD}\u{FFFD}ok\u{FFFD}"), "{}", err);
+        let err = git.handle(get_jobs(1, None, None), &mut context()).await.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().ends_with(" in \u{FFFD}\u{FFFD}ok\u{FFFD}"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn compressed_listings_are_decoded() {
+        let server = MockServer::start(|_| MockResponse::gzip_json(200, serde_json::json!([
+            { "id": 1, "created_at": "2024-01-01T00:00:00Z", "status": "failed", "ref": "main" },
+        ]))).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let response = git.handle(get_jobs(1, None, None), &mut context()).await.unwrap();
+        assert_eq!(response.jobs.iter().map(|job| job.id).collect::<Vec<_>>(), vec![1]);
+        let accepted = server.requests.lock().unwrap()[0].header("Accept-Encoding").unwrap_or_default().to_string();
+        assert!(accepted.contains("gzip") && accepted.contains("br"), "{}", accepted);
+    }
+
+    #[tokio::test]
+    async fn no_content_is_a_successful_erase() {
+        let server = MockServer::start(|_| MockResponse::empty(204)).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        assert_eq!(git.handle(EraseJob { project_id: 1, job_id: 2 }, &mut context()).await.unwrap(), EraseOutcome::Erased);
+        assert_eq!(git.handle(DeleteJobArtifacts { project_id: 1, job_id: 2 }, &mut context()).await.unwrap(), EraseOutcome::Erased);
+        assert_eq!(git.handle(DeletePipeline { project_id: 1, pipeline_id: 3 }, &mut context()).await.unwrap(), EraseOutcome::Erased);
+    }
+
+    #[test]
+    fn next_link_finds_the_next_page() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(next_link(&headers), None);
+        headers.insert(LINK, "<https://host/jobs?cursor=a>; rel=\"first\", <https://host/jobs?cursor=b>; rel=\"next\"".parse().unwrap());
+        assert_eq!(next_link(&headers).as_deref(), Some("https://host/jobs?cursor=b"));
+        headers.insert(LINK, "<https://host/jobs?cursor=a>; rel=\"first\"".parse().unwrap());
+        assert_eq!(next_link(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn keyset_pages_follow_the_cursor_of_the_link_header() {
+        // The links are absolute, so they are only known once the instance is listening.
+        let url = Arc::new(Mutex::new(String::new()));
+        let base_url = url.clone();
+        let server = MockServer::start(move |request| {
+            let job = |id: u64| serde_json::json!({ "id": id, "created_at": "2024-01-01T00:00:00Z", "status": "failed", "ref": "main" });
+            match request.query("cursor") {
+                None => MockResponse::json(200, serde_json::json!([job(11), job(12)]))
+                    .header("Link", format!("<{}/api/v4/projects/1/jobs?cursor=b>; rel=\"next\"", base_url.lock().unwrap())),
+                Some(_) => MockResponse::json(200, serde_json::json!([job(13)])),
+            }
+        }).await;
+        *url.lock().unwrap() = server.url.clone();
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let first = git.handle(GetJobs { keyset: true, after_id: Some(10), ..get_jobs(1, None, None) }, &mut context()).await.unwrap();
+        assert_eq!((first.jobs.len(), first.next_page), (2, Some(2)));
+        let second = git.handle(GetJobs { keyset: true, after_id: Some(10), ..get_jobs(2, None, None) }, &mut context()).await.unwrap();
+        assert_eq!((second.jobs.iter().map(|job| job.id).collect::<Vec<_>>(), second.next_page), (vec![13], None));
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!((requests[0].query("pagination"), requests[0].query("sort"), requests[0].query("id_after")), (Some("keyset"), Some("asc"), Some("10")));
+        assert_eq!(requests[1].path, "/api/v4/projects/1/jobs?cursor=b");
+    }
+
+    #[tokio::test]
+    async fn a_missing_listing_is_not_found() {
+        let server = MockServer::start(|_| MockResponse::json(404, serde_json::json!({ "message": "404 Not Found" }))).await;
+        let mut git = git(&server, Arc::new(SystemClock), GitConfig::default());
+        let err = git.handle(GetPackages { project_id: 1, page: 1 }, &mut context()).await.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn validate_base_url_keeps_the_sub_path() {
+        assert_eq!(validate_base_url("https://gitlab.example.com/").unwrap(), "https://gitlab.example.com");
+        assert_eq!(validate_base_url("https://host//gitlab/?page=1#top").unwrap(), "https://host/gitlab");
+        assert_eq!(validate_base_url("gitlab.example.com").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(validate_base_url("ftp://gitlab.example.com").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn requests_keep_the_sub_path_of_the_instance() {
+        let server = MockServer::start(|_| MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project" }))).await;
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        for base_url in [format!("{}/gitlab", server.url), format!("{}/gitlab/api/v4/", server.url)] {
+            let mut git = Git::new(GitConfig {
+                base_url,
+                token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+                ..GitConfig::default()
+            }).unwrap();
+            git.handle(GetProjectById { project_id: 1 }, &mut context()).await.unwrap();
+        }
+        assert_eq!(server.paths("GET"), vec!["/gitlab/api/v4/projects/1", "/gitlab/api/v4/projects/1"]);
+    }
+}
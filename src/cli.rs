@@ -0,0 +1,846 @@
+use std::{fmt::{Display, self, Formatter}, io::IsTerminal, path::PathBuf, sync::Arc, time::Duration};
+
+use chrono::{Utc, DateTime};
+use clap::{CommandFactory, Parser, ValueEnum};
+use clap_complete::Shell;
+
+use crate::{actors::{displ::{self, OutputFormat}, git::{AgeField, Backoff}}, clock::{Clock, FixedClock, SkewedClock, SystemClock}, select::Selection};
+
+/// The number of items erased concurrently without `--concurrency`.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Enum used to define the target component(s) of the project to clean.
+#[derive(Parser, Debug, Clone, PartialEq, ValueEnum)]
+pub enum Target {
+    /// The target is the jobs of the project.
+    Jobs,
+    /// The target is the pipelines of the project.
+    Pipelines,
+    /// The target is the deployments of an environment of the project.
+    Deployments,
+    /// The target is the package versions of the project's package registry.
+    Packages,
+    /// The target is the artifacts of the jobs of the project, keeping the jobs and their logs.
+    Artifacts,
+}
+
+impl Target {
+    /// The minimum Gitlab version (major, minor) providing the endpoints used by the target.
+    pub fn minimum_gitlab_version(&self) -> (u64, u64) {
+        match self {
+            // The jobs API (formerly builds) and its erase endpoint are available since 9.0.
+            Target::Jobs => (9, 0),
+            // The pipeline deletion endpoint is available since 11.6.
+            Target::Pipelines => (11, 6),
+            // The deployment deletion endpoint is available since 15.3.
+            Target::Deployments => (15, 3),
+            // The package deletion endpoint is available since 11.9.
+            Target::Packages => (11, 9),
+            // The job artifacts deletion endpoint is available since 11.9.
+            Target::Artifacts => (11, 9),
+        }
+    }
+
+    /// The listing endpoint of the project fetched by the target, e.g. `jobs`.
+    pub fn listing(&self) -> &'static str {
+        match self {
+            Target::Jobs | Target::Artifacts => "jobs",
+            Target::Pipelines => "pipelines",
+            Target::Deployments => "deployments",
+            Target::Packages => "packages",
+        }
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Jobs => write!(f, "jobs"),
+            Target::Pipelines => write!(f, "pipelines"),
+            Target::Deployments => write!(f, "deployments"),
+            Target::Packages => write!(f, "packages"),
+            Target::Artifacts => write!(f, "artifacts"),
+        }
+    }
+}
+
+/// Enum used to define how the permissions are probed in a dry run.
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum ProbeMode {
+    /// Send a HEAD request on the job, which modifies nothing but only proves that the token can read it.
+    Head,
+    /// Really erase the job, the only one erased by the dry run.
+    Erase,
+}
+
+/// Enum used to define how the candidates of a dry run are grouped.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum DryRunGroup {
+    /// Group by the git ref of the jobs and pipelines.
+    Ref,
+    /// Group by the status of the jobs and pipelines.
+    Status,
+}
+
+/// Enum used to define when the output uses ANSI colors.
+#[derive(Debug, Clone, ValueEnum)]
+pub enum ColorChoice {
+    /// Never use colors.
+    Never,
+    /// Use colors when the output is a terminal.
+    Auto,
+    /// Always use colors.
+    Always,
+}
+
+impl ColorChoice {
+    /// Whether the colors are enabled, detecting the terminal in auto mode.
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorChoice::Never => false,
+            ColorChoice::Auto => std::io::stderr().is_terminal(),
+            ColorChoice::Always => true,
+        }
+    }
+}
+
+/// The arguments of the command line, used as the configuration of the run.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+
+    /// The name(s) of the project(s) to search for, a numeric one being taken as a project id. Can be repeated or comma separated.
+    #[arg(short, long, required_unless_present_any = ["shell_completion", "list_targets", "all_group_projects", "project_id"], value_delimiter = ',')]
+    pub project: Vec<String>,
+
+    /// The id(s) of the project(s) to clean, checked to exist before cleaning. Can be repeated or comma separated.
+    #[arg(long, value_delimiter = ',')]
+    pub project_id: Vec<u64>,
+
+    /// Search the numeric `--project` names as names too, instead of taking them as project ids.
+    #[arg(long)]
+    pub force_search: bool,
+
+    /// The group of the project to search for.
+    #[arg(short, long)]
+    pub group: Option<String>,
+
+    /// When a project name matches several projects, list them and prompt for the one to clean instead of failing.
+    #[arg(long)]
+    pub interactive: bool,
+
+    /// With `--interactive`, the maximum number of matching projects listed.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..=100))]
+    pub project_search_limit: u64,
+
+    /// Clean all the projects of the group, including its subgroups, instead of the searched projects.
+    #[arg(long, requires = "group", conflicts_with_all = ["project", "project_id", "inspect_job"])]
+    pub all_group_projects: bool,
+
+    /// With `--all-group-projects`, only clean the projects with one of these visibilities. Can be repeated or comma separated.
+    #[arg(long, requires = "all_group_projects", value_delimiter = ',', value_parser = ["public", "internal", "private"])]
+    pub visibility: Vec<String>,
+
+    /// With `--all-group-projects`, also clean the archived projects, skipped otherwise.
+    #[arg(long, requires = "all_group_projects")]
+    pub include_archived: bool,
+
+    /// Skip a target of a project whose listing is not found (404), e.g. a project without package registry,
+    /// instead of failing the whole run. Meant for the runs over many projects.
+    #[arg(long)]
+    pub ignore_404_on_list: bool,
+
+    /// With `--all-group-projects`, skip the projects with some activity within this number of days.
+    #[arg(long, requires = "all_group_projects")]
+    pub min_project_idle_days: Option<u64>,
+
+    /// The target component(s) of the project to clean. Can be repeated or comma separated.
+    #[clap(value_enum)]
+    #[arg(short, long, default_value = "jobs", value_delimiter = ',')]
+    pub target: Vec<Target>,
+
+    /// Clean the targets concurrently instead of one after the other.
+    #[arg(long)]
+    pub parallel_targets: bool,
+
+    /// The expiration date of the component(s) to clean.
+    #[arg(value_parser = parse_duration, default_value = "365")]
+    pub expiration_in_days: std::time::Duration,
+
+    /// The base url of the Gitlab API. `${VAR}` references are expanded from the environment.
+    #[arg(long, env = "GITLAB_URL", required_unless_present_any = ["shell_completion", "list_targets"])]
+    pub gitlab_url: Option<String>,
+
+    /// The path of the API, appended to the Gitlab url when missing. An empty path disables the suffixing.
+    #[arg(long, default_value = "/api/v4")]
+    pub api_path: String,
+
+    /// Additional header sent with every request, as `NAME:VALUE`. Can be repeated.
+    /// `${VAR}` references in the value are expanded from the environment.
+    #[arg(long = "header", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// The first page of jobs to fetch. Overrides the default of starting at the first page.
+    #[arg(long)]
+    pub page_start: Option<u64>,
+
+    /// Traverse the jobs by id with the keyset pagination, so that the jobs created meanwhile do not shift the pages,
+    /// which makes jobs fetched twice or missed. Requires Gitlab 15.9.
+    #[arg(long, conflicts_with = "page_start")]
+    pub keyset_pagination: bool,
+
+    /// The number of pages of a listing fetched at once, their items being merged in the page order.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..=20), conflicts_with = "keyset_pagination")]
+    pub parallel_pages: u64,
+
+    /// With `--keyset-pagination`, resume the traversal of the jobs after this job id, from the oldest to the newest
+    /// (`id_after` cursor), e.g. to continue an interrupted run.
+    #[arg(long, requires = "keyset_pagination", conflicts_with_all = ["only_superseded", "keep_per_ref"])]
+    pub after_id: Option<u64>,
+
+    /// The last page of jobs to fetch (inclusive). Overrides following the next pages until none is left.
+    #[arg(long)]
+    pub page_end: Option<u64>,
+
+    /// The maximum number of pages fetched per listing, whatever the next pages announced by the Gitlab API.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_pages: Option<u64>,
+
+    /// Only clean the component(s) created before this RFC3339 date. Overrides the expiration in days.
+    #[arg(long, value_parser = parse_date)]
+    pub created_before: Option<DateTime<Utc>>,
+
+    /// Only clean the component(s) created after this RFC3339 date. Overrides the expiration in days.
+    #[arg(long, value_parser = parse_date)]
+    pub created_after: Option<DateTime<Utc>>,
+
+    /// The file storing the cutoff of the last successful run, i.e. the date the component(s) had to be created before,
+    /// updated on success unless --limit, --max-pages, --page-end or --per-status-limit left some of them out.
+    #[arg(long)]
+    pub state_file: Option<PathBuf>,
+
+    /// Only clean the component(s) created after the cutoff of the last successful run recorded in the state file, i.e.
+    /// the ones expired since. Everything up to the cutoff is considered on the first run.
+    #[arg(long, requires = "state_file")]
+    pub since_last_run: bool,
+
+    /// The date of the jobs the expiration and the date window apply to: their creation date, or the date they
+    /// finished (falling back to the creation date for unfinished jobs).
+    #[clap(value_enum)]
+    #[arg(long, default_value = "created")]
+    pub age_field: AgeField,
+
+    /// Only clean the pipelines with one of these statuses (e.g. `failed`, `canceled`). Can be repeated.
+    #[arg(long)]
+    pub pipeline_status: Vec<String>,
+
+    /// Only erase the jobs with one of these statuses (e.g. `failed`, `canceled`). Can be repeated.
+    #[arg(long)]
+    pub status: Vec<String>,
+
+    /// Never erase the jobs with one of these statuses, applied after `--status`: a status both included and excluded is excluded. Can be repeated.
+    #[arg(long)]
+    pub exclude_status: Vec<String>,
+
+    /// Only erase the jobs whose pipeline has one of these statuses (e.g. `failed` for the jobs of the failed pipelines).
+    /// Can be repeated.
+    #[arg(long)]
+    pub by_pipeline_status: Vec<String>,
+
+    /// The maximum number of jobs erased with the given status, as `STATUS=N` (e.g. `failed=500`). Can be repeated.
+    /// The jobs with other statuses are not limited.
+    #[arg(long, value_parser = parse_status_limit)]
+    pub per_status_limit: Vec<(String, u64)>,
+
+    /// The maximum number of jobs erased per project, over all the statuses.
+    #[arg(long)]
+    pub limit: Option<u64>,
+
+    /// The statuses of the jobs erased first under `--limit`, by decreasing priority (e.g. `failed,canceled,success`).
+    /// The jobs with other statuses come last. With `--max-buffered-jobs`, the priority holds within the buffered jobs.
+    #[arg(long, value_delimiter = ',', requires = "limit")]
+    pub status_priority: Vec<String>,
+
+    /// Keep the successful pipelines and jobs. Takes precedence over `--pipeline-status` and `--status`, the successful
+    /// ones being kept even when they list `success`.
+    #[arg(long)]
+    pub keep_success: bool,
+
+    /// The environment of the deployments to clean. Required by the deployments target.
+    #[arg(long)]
+    pub environment: Option<String>,
+
+    /// Whether the listings are assumed sorted newest first, which allows to stop fetching pages as soon
+    /// as a page reaches items created before `--created-after`.
+    #[arg(long, visible_alias = "assume-newest-first", default_value_t = true, action = clap::ArgAction::Set)]
+    pub assume_sorted: bool,
+
+    /// The number of latest versions of each package that are kept, whatever their age.
+    #[arg(long, default_value = "0")]
+    pub keep_latest_versions: usize,
+
+    /// Only clean the jobs whose artifacts already expired, keeping the jobs with live or no artifacts.
+    #[arg(long)]
+    pub include_artifacts_expired_only: bool,
+
+    /// Only clean the jobs with artifacts or a log to reclaim, skipping the jobs whose artifacts size is zero.
+    #[arg(long)]
+    pub only_with_artifacts: bool,
+
+    /// Only clean the jobs with an id greater or equal to this one, on top of the date window.
+    #[arg(long)]
+    pub min_job_id: Option<u64>,
+
+    /// Only clean the jobs with an id lower or equal to this one, on top of the date window.
+    #[arg(long)]
+    pub max_job_id: Option<u64>,
+
+    /// Only erase the jobs selected by this expression over their fields `status`, `ref`, `name`, `pipeline_status`
+    /// (compared to quoted texts with `==` or `!=`), `id`, `size` (compared to numbers) and `age` (compared to durations,
+    /// e.g. `30d`), combined with `&&`, `||`, `!` and parentheses. Applies along with the other filters.
+    #[arg(long, value_name = "EXPRESSION", value_parser = Selection::parse)]
+    pub select: Option<Selection>,
+
+    /// Poll the jobs whose erase was accepted asynchronously (202 Accepted) until they are erased.
+    #[arg(long)]
+    pub verify: bool,
+
+    /// The maximum number of seconds to wait for an accepted erase to be done with `--verify`.
+    #[arg(long, default_value_t = 30)]
+    pub verify_timeout_secs: u64,
+
+    /// Ask for a confirmation before erasing more than this number of items of a target. The threshold holds on all
+    /// the candidates of the target, which are not known in advance when the jobs are buffered.
+    #[arg(long, conflicts_with = "max_buffered_jobs")]
+    pub confirm_threshold: Option<u64>,
+
+    /// Print the first N candidates of each target before erasing them, then ask for a confirmation unless `--yes`.
+    /// The candidates of all the targets of a project are previewed and confirmed together.
+    #[arg(long, conflicts_with_all = ["dry_run", "max_buffered_jobs"])]
+    pub preview: Option<usize>,
+
+    /// Answer yes to the confirmations, e.g. in non interactive runs.
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// The number of final passes re-attempting the items that failed to be erased, with a growing backoff.
+    #[arg(long, default_value_t = 0)]
+    pub retry_failed: u32,
+
+    /// Only clean the superseded attempts of the retried jobs, keeping the latest attempt of each job name and ref
+    /// within the window.
+    #[arg(long)]
+    pub only_superseded: bool,
+
+    /// Keep the newest N jobs of each ref (branch or tag) within the window, cleaning the older ones.
+    #[arg(long)]
+    pub keep_per_ref: Option<u64>,
+
+    /// With the artifacts target, only delete the artifacts of the jobs having an artifact of one of these types
+    /// (e.g. `archive`, `junit`). Can be repeated. The API deletes all the artifacts of a job but its log, whatever their type.
+    #[arg(long)]
+    pub artifact_type: Vec<String>,
+
+    /// The maximum number of jobs buffered in memory: once no further page fits, they are erased before fetching the
+    /// next pages. Below 50, the pages of jobs hold at most that many jobs, which shifts the numbering of --page-start
+    /// and --page-end.
+    #[arg(long)]
+    pub max_buffered_jobs: Option<usize>,
+
+    /// Only clean the jobs of a project that has at least this number of jobs in total.
+    #[arg(long)]
+    pub min_jobs: Option<u64>,
+
+    /// The maximum number of items erased concurrently, also bounding the idle connections kept to the Gitlab instance.
+    /// Defaults to 8.
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub concurrency: Option<usize>,
+
+    /// Erase the items one at a time in the order of their ids, so that the logs of two runs can be compared,
+    /// at the cost of the throughput.
+    #[arg(long, conflicts_with = "concurrency")]
+    pub ordered: bool,
+
+    /// Pause the erases until the reset of the rate limit when its remaining requests (`RateLimit-Remaining`) fall to this number.
+    #[arg(long)]
+    pub pause_on_rate_limit_threshold: Option<u64>,
+
+    /// The timeout in seconds of every request sent to the Gitlab API.
+    #[arg(long)]
+    pub timeout_secs: Option<u64>,
+
+    /// The timeout in seconds of the job erase requests, which can take longer on large artifacts. Overrides `--timeout-secs`.
+    #[arg(long)]
+    pub erase_timeout_secs: Option<u64>,
+
+    /// The number of times a request failing to connect (e.g. refused, or the connections exhausted) is retried.
+    #[arg(long, default_value_t = 3)]
+    pub connect_retries: u32,
+
+    /// The longest wait in seconds before a single retry, capping the exponential backoff of the retries.
+    #[arg(long)]
+    pub max_backoff_secs: Option<u64>,
+
+    /// The longest cumulative wait in seconds over the retries of a request or of the failed items, after which retrying is given up with a timeout error.
+    #[arg(long)]
+    pub max_total_wait_secs: Option<u64>,
+
+    /// Erase the jobs in batches of this size instead of all at once.
+    #[arg(long)]
+    pub batch_size: Option<usize>,
+
+    /// The pause in seconds between two batches of erased jobs.
+    #[arg(long, default_value = "0")]
+    pub batch_pause_secs: u64,
+
+    /// Accept invalid certificates from the Gitlab instance. Dangerous, only meant for self-signed instances.
+    #[arg(long)]
+    pub insecure: bool,
+
+    /// The path of an additional PEM root certificate to trust, e.g. an internal CA.
+    #[arg(long)]
+    pub ca_cert: Option<PathBuf>,
+
+    /// The name of the environment variable holding the Gitlab token.
+    #[arg(long, default_value = "GITLAB_TOKEN")]
+    pub token_env: String,
+
+    /// Print each request sent to the Gitlab API as an equivalent curl command (token redacted).
+    #[arg(long)]
+    pub dump_requests: bool,
+
+    /// Skip the detection of the Gitlab version at startup.
+    #[arg(long)]
+    pub skip_version_check: bool,
+
+    /// Whether the output uses ANSI colors.
+    #[clap(value_enum)]
+    #[arg(long, default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// The format of the output: human readable text or one JSON object per line.
+    #[clap(value_enum)]
+    #[arg(long, default_value = "text")]
+    pub output: OutputFormat,
+
+    /// Print the ids of the erased items on stdout, one per line, all the other messages and the events of the ndjson
+    /// output going to stderr.
+    #[arg(long)]
+    pub print_ids: bool,
+
+    /// Stay silent when the run succeeds, only printing the messages of the run when it fails (e.g. for cron jobs).
+    #[arg(long)]
+    pub report_only_failures: bool,
+
+    /// Hide the per item messages, only keeping the progress and the final report.
+    #[arg(long)]
+    pub summary_only: bool,
+
+    /// The maximum number of redraws per second of the progress bar, clamped between 1 and 60.
+    #[arg(long, default_value_t = displ::DEFAULT_REFRESH_HZ)]
+    pub progress_refresh_hz: u8,
+
+    /// Exit with a nonzero code when no component matched the filters.
+    #[arg(long)]
+    pub fail_on_empty: bool,
+
+    /// Write each event of the run as a JSON log line on stderr, e.g. for log aggregators.
+    #[arg(long)]
+    pub log_json: bool,
+
+    /// Print a summary of the requests (count, latencies, downloaded bytes) and the elapsed time at the end.
+    #[arg(long)]
+    pub metrics: bool,
+
+    /// Check the connectivity, the token and the target endpoints of the project without deleting anything.
+    #[arg(long)]
+    pub health_check: bool,
+
+    /// Print the completion script of the given shell on stdout, then exit.
+    #[clap(value_enum)]
+    #[arg(long)]
+    pub shell_completion: Option<Shell>,
+
+    /// Print the supported targets with a description of each on stdout, then exit.
+    #[arg(long)]
+    pub list_targets: bool,
+
+    /// List the component(s) that would be cleaned without erasing anything.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// With `--dry-run`, probe whether the token is authorized on the newest job candidate: `head` only checks that
+    /// the token can read the job, not that it can erase it, `erase` really erases that single job.
+    #[clap(value_enum)]
+    #[arg(long, requires = "dry_run")]
+    pub dry_run_probe: Option<ProbeMode>,
+
+    /// With `--dry-run`, group the candidates by `ref` or `status`, printing the count and a sample of each group
+    /// instead of every candidate. The candidates are always listed from the oldest.
+    #[clap(value_enum)]
+    #[arg(long, requires = "dry_run")]
+    pub dry_run_group_by: Option<DryRunGroup>,
+
+    /// Write the ids of the matched component(s) of each target to this JSON snapshot file, keyed by target, as
+    /// `<project id>/<target>` when several projects are cleaned.
+    #[arg(long)]
+    pub snapshot_out: Option<PathBuf>,
+
+    /// Compare the matched component(s) with this previous snapshot, printing the added and removed ids.
+    #[arg(long)]
+    pub snapshot_in: Option<PathBuf>,
+
+    /// Erase exactly the jobs of this snapshot, written by `--dry-run --snapshot-out` and reviewed meanwhile, without
+    /// listing nor filtering the jobs. Nothing is erased when the snapshot lacks one of the projects cleaned.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["dry_run", "inspect_job", "interval"])]
+    pub apply_file: Option<PathBuf>,
+
+    /// Write the candidate jobs of all the projects, with all their fields, to this file before erasing them, whatever
+    /// the output and even with `--dry-run`. Written as CSV when the file has the `.csv` extension, as JSON otherwise.
+    #[arg(long, value_name = "PATH")]
+    pub export_candidates: Option<PathBuf>,
+
+    /// Write the summary of the run (dates, counts of each project and totals) to this JSON file, whatever the output.
+    #[arg(long)]
+    pub summary_out: Option<PathBuf>,
+
+    /// Post the summary of the run as JSON to this url once done, whether it succeeded or not. A failure to post is only reported.
+    #[arg(long)]
+    pub webhook_url: Option<String>,
+
+    /// Post a Slack-compatible message (`{"text": ...}`) to the webhook instead of the JSON summary.
+    #[arg(long, requires = "webhook_url")]
+    pub webhook_slack: bool,
+
+    /// Fetch and print the current state of a single job of the project, then exit without cleaning.
+    #[arg(long, conflicts_with_all = ["export_candidates", "print_ids"])]
+    pub inspect_job: Option<u64>,
+
+    /// The RFC3339 date used as the current date to compute the cutoff, for deterministic reproductions.
+    #[arg(long, hide = true, value_parser = parse_date)]
+    pub pretend_now: Option<DateTime<Utc>>,
+
+    /// The seconds added to the local date to compute the cutoff, negative when the local clock is ahead of the
+    /// Gitlab instance.
+    #[arg(long, default_value_t = 0, allow_hyphen_values = true, conflicts_with = "use_server_time")]
+    pub clock_skew_secs: i64,
+
+    /// Compute the cutoff from the date of the Gitlab instance (its `Date` response header) rather than the local clock.
+    #[arg(long, conflicts_with = "pretend_now")]
+    pub use_server_time: bool,
+
+    /// Run continuously, cleaning again after this interval (e.g. `30m`, `6h` or `1d`) until Ctrl-C, which stops
+    /// once the current cycle is over.
+    #[arg(long = "loop", value_name = "INTERVAL", value_parser = parse_interval, conflicts_with_all = ["inspect_job", "health_check", "interactive"])]
+    pub interval: Option<std::time::Duration>,
+
+    /// With `--loop`, how long in seconds a resolved project id is kept before being resolved again, forever when not set.
+    #[arg(long, requires = "interval")]
+    pub project_cache_ttl_secs: Option<u64>,
+}
+
+impl Args {
+    /// The names of the projects to search for, the numeric ones being ids unless `--force-search`.
+    pub fn project_names(&self) -> Vec<&String> {
+        self.project.iter()
+            .filter(|name| self.force_search || name.parse::<u64>().is_err())
+            .collect()
+    }
+
+    /// The ids of the projects to clean, given with `--project-id` or as numeric `--project` names.
+    pub fn project_ids(&self) -> Vec<u64> {
+        let numeric_names = self.project.iter()
+            .filter(|_| !self.force_search)
+            .filter_map(|name| name.parse::<u64>().ok());
+        numeric_names.chain(self.project_id.iter().copied()).collect()
+    }
+
+    /// The number of items erased at once: one in ordered mode, the default concurrency without `--concurrency`.
+    pub fn concurrency(&self) -> usize {
+        if self.ordered { 1 } else { self.concurrency.unwrap_or(DEFAULT_CONCURRENCY) }
+    }
+
+    /// The number of Git actors sending the requests, enough for the pages fetched or the items erased at once by each
+    /// of the targets cleaned concurrently.
+    pub fn git_actors(&self) -> usize {
+        let targets = if self.parallel_targets { self.target.len() } else { 1 };
+        (self.parallel_pages as usize).max(self.concurrency()) * targets
+    }
+
+    /// The bounds of the waits between retries set on the command line.
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            max_delay: self.max_backoff_secs.map(Duration::from_secs),
+            max_total_wait: self.max_total_wait_secs.map(Duration::from_secs),
+        }
+    }
+
+    /// The clock of the process, before any skew: fixed at the pretended date, if any.
+    pub fn base_clock(&self) -> Arc<dyn Clock> {
+        match self.pretend_now {
+            Some(now) => Arc::new(FixedClock(now)),
+            None => Arc::new(SystemClock),
+        }
+    }
+
+    /// The clock the cutoff is computed from: the base clock shifted by the clock skew, if any.
+    pub fn clock(&self, base_clock: Arc<dyn Clock>) -> Arc<dyn Clock> {
+        match self.clock_skew_secs {
+            0 => base_clock,
+            skew => Arc::new(SkewedClock { clock: base_clock, skew: chrono::Duration::seconds(skew) }),
+        }
+    }
+}
+
+/// Parse the arguments of the command line, exiting with a usage error when they are inconsistent.
+/// With `--shell-completion`, the completion script is printed and the process exits.
+pub fn parse() -> Args {
+    let args = Args::parse();
+    if let Some(shell) = args.shell_completion {
+        clap_complete::generate(shell, &mut Args::command(), env!("CARGO_PKG_NAME"), &mut std::io::stdout());
+        std::process::exit(0);
+    }
+    if args.list_targets {
+        for target in Target::value_variants() {
+            let help = target.to_possible_value().and_then(|value| value.get_help().map(|help| help.to_string()));
+            println!("{:<12} {}", target.to_string(), help.unwrap_or_default());
+        }
+        std::process::exit(0);
+    }
+    if let Err(err) = validate(&args) {
+        err.exit();
+    }
+    args
+}
+
+/// Check the consistency of the arguments beyond what clap checks, e.g. the ranges and the targets combinations.
+pub fn validate(args: &Args) -> Result<(), clap::Error> {
+    if let (Some(start), Some(end)) = (args.page_start, args.page_end) {
+        if start > end {
+            return Err(Args::command()
+                .error(clap::error::ErrorKind::ArgumentConflict, "--page-start must be lower or equal to --page-end."));
+        }
+    }
+    if let (Some(before), Some(after)) = (args.created_before, args.created_after) {
+        if after >= before {
+            return Err(Args::command()
+                .error(clap::error::ErrorKind::ArgumentConflict, "--created-after must be before --created-before."));
+        }
+    }
+    if let (Some(min), Some(max)) = (args.min_job_id, args.max_job_id) {
+        if min > max {
+            return Err(Args::command()
+                .error(clap::error::ErrorKind::ArgumentConflict, "--min-job-id must be lower or equal to --max-job-id."));
+        }
+    }
+    if args.target.contains(&Target::Deployments) && args.environment.is_none() {
+        return Err(Args::command()
+            .error(clap::error::ErrorKind::MissingRequiredArgument, "--environment is required by the deployments target."));
+    }
+    // Erasing the jobs also deletes their artifacts, and deleting the pipelines also deletes their jobs.
+    let overlapping = [(Target::Jobs, Target::Artifacts), (Target::Pipelines, Target::Jobs), (Target::Pipelines, Target::Artifacts)];
+    if let Some((first, second)) = overlapping.iter().find(|(first, second)| args.target.contains(first) && args.target.contains(second)) {
+        if args.parallel_targets {
+            return Err(Args::command()
+                .error(clap::error::ErrorKind::ArgumentConflict, format!(
+                    "--parallel-targets cannot clean the {} and the {} concurrently, they touch the same jobs.", first, second)));
+        }
+    }
+    if args.apply_file.is_some() && args.target.iter().any(|target| *target != Target::Jobs) {
+        return Err(Args::command()
+            .error(clap::error::ErrorKind::ArgumentConflict, "--apply-file only applies to the jobs target."));
+    }
+    if args.inspect_job.is_some() && args.project.len() + args.project_id.len() > 1 {
+        return Err(Args::command()
+            .error(clap::error::ErrorKind::ArgumentConflict, "--inspect-job requires a single project."));
+    }
+    Ok(())
+}
+
+/// Parse a duration from a days count.
+fn parse_duration(arg: &str) -> Result<std::time::Duration, String> {
+    let days : u64 = arg.parse().map_err(|err: std::num::ParseIntError| err.to_string())?;
+    // The cutoff must remain a valid date, which it does from any date after the epoch.
+    days.checked_mul(60 * 60 * 24)
+        .map(std::time::Duration::from_secs)
+        .filter(|duration| chrono::Duration::from_std(*duration).ok()
+            .and_then(|duration| DateTime::UNIX_EPOCH.checked_sub_signed(duration)).is_some())
+        .ok_or(format!("Invalid expiration '{}', too large.", arg))
+}
+
+/// Parse an interval from a number followed by a unit: `s`, `m`, `h` or `d`, seconds when missing (e.g. `6h`).
+pub fn parse_interval(arg: &str) -> Result<std::time::Duration, String> {
+    let (number, unit) = arg.split_at(arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len()));
+    let number: u64 = number.parse().map_err(|_| format!("Invalid interval '{}', expected e.g. 30s, 15m, 6h or 1d.", arg))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => return Err(format!("Invalid unit of the interval '{}', expected s, m, h or d.", arg)),
+    };
+    let seconds = number.checked_mul(seconds)
+        .ok_or(format!("Invalid interval '{}', too large.", arg))?;
+    Ok(std::time::Duration::from_secs(seconds))
+}
+
+/// Parse a date from an RFC3339 string.
+pub fn parse_date(arg: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    Ok(DateTime::parse_from_rfc3339(arg)?.with_timezone(&Utc))
+}
+
+/// Parse a header from a `NAME:VALUE` string.
+fn parse_header(arg: &str) -> Result<(String, String), String> {
+    let (name, value) = arg.split_once(':')
+        .ok_or(format!("Invalid header '{}', expected NAME:VALUE.", arg))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+/// Parse a limit of a status from a `STATUS=N` string.
+fn parse_status_limit(arg: &str) -> Result<(String, u64), String> {
+    let (status, limit) = arg.split_once('=')
+        .ok_or(format!("Invalid status limit '{}', expected STATUS=N.", arg))?;
+    let limit = limit.trim().parse::<u64>()
+        .map_err(|_| format!("Invalid limit '{}' for the status {}, expected a number.", limit, status))?;
+    Ok((status.trim().to_string(), limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn try_parse(extra: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(["gitlab-cleaner", "--gitlab-url", "https://host"].iter().chain(extra))
+            .and_then(|args| validate(&args).map(|_| args))
+    }
+
+    /// The kind of the error of the arguments given on top of a project, if any.
+    fn error_kind(extra: &[&str]) -> Option<clap::error::ErrorKind> {
+        try_parse(&[&["-p", "project"], extra].concat()).err().map(|err| err.kind())
+    }
+
+    #[test]
+    fn parse_duration_rejects_an_overflow() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30 * 24 * 60 * 60)));
+        assert!(parse_duration("9999999999999").is_err());
+        assert!(parse_duration("-1").is_err());
+    }
+
+    #[test]
+    fn parses_the_defaults() {
+        let args = try_parse(&["-p", "group/project", "-p", "42"]).unwrap();
+        assert_eq!(args.target, vec![Target::Jobs]);
+        assert_eq!(args.project_names(), vec!["group/project"]);
+        assert_eq!(args.project_ids(), vec![42]);
+        assert!(!args.dry_run);
+    }
+
+    #[test]
+    fn parses_the_targets_and_the_repeated_arguments() {
+        let args = try_parse(&["-p", "project", "-t", "jobs,packages", "--per-status-limit", "failed=5", "--header", "X-A: 1"]).unwrap();
+        assert_eq!(args.target, vec![Target::Jobs, Target::Packages]);
+        assert_eq!(args.per_status_limit, vec![("failed".to_string(), 5)]);
+        assert_eq!(args.headers, vec![("X-A".to_string(), "1".to_string())]);
+    }
+
+    #[test]
+    fn requires_a_project() {
+        assert_eq!(try_parse(&[]).unwrap_err().kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn parse_header_splits_at_the_first_colon() {
+        assert_eq!(parse_header("X-Trace: a:b"), Ok(("X-Trace".to_string(), "a:b".to_string())));
+        assert!(parse_header("X-Trace").is_err());
+    }
+
+    #[test]
+    fn the_page_range_is_ordered() {
+        assert_eq!(error_kind(&["--page-start", "3", "--page-end", "2"]), Some(clap::error::ErrorKind::ArgumentConflict));
+        assert_eq!(error_kind(&["--page-start", "2", "--page-end", "2"]), None);
+    }
+
+    #[test]
+    fn the_date_window_is_ordered() {
+        assert_eq!(error_kind(&["--created-before", "2024-01-01T00:00:00Z", "--created-after", "2024-02-01T00:00:00Z"]),
+            Some(clap::error::ErrorKind::ArgumentConflict));
+        assert_eq!(error_kind(&["--created-before", "2024-02-01T00:00:00Z", "--created-after", "2024-01-01T00:00:00Z"]), None);
+        assert_eq!(error_kind(&["--created-before", "yesterday"]), Some(clap::error::ErrorKind::ValueValidation));
+    }
+
+    #[test]
+    fn a_job_is_inspected_in_a_single_project() {
+        assert_eq!(error_kind(&["--inspect-job", "5"]), None);
+        assert_eq!(error_kind(&["--inspect-job", "5", "-p", "other"]), Some(clap::error::ErrorKind::ArgumentConflict));
+    }
+
+    #[test]
+    fn colors_follow_the_choice() {
+        assert!(!ColorChoice::Never.enabled());
+        assert!(ColorChoice::Always.enabled());
+        assert!(matches!(try_parse(&["-p", "project"]).unwrap().color, ColorChoice::Auto));
+        assert!(matches!(try_parse(&["-p", "project", "--color", "never"]).unwrap().color, ColorChoice::Never));
+    }
+
+    #[test]
+    fn deployments_require_an_environment() {
+        assert_eq!(error_kind(&["-t", "deployments"]), Some(clap::error::ErrorKind::MissingRequiredArgument));
+        assert_eq!(error_kind(&["-t", "deployments", "--environment", "review"]), None);
+    }
+
+    #[test]
+    fn overlapping_targets_are_not_cleaned_in_parallel() {
+        assert_eq!(error_kind(&["-t", "jobs,artifacts", "--parallel-targets"]), Some(clap::error::ErrorKind::ArgumentConflict));
+        assert_eq!(error_kind(&["-t", "jobs,packages", "--parallel-targets"]), None);
+    }
+
+    #[test]
+    fn the_confirm_threshold_needs_the_whole_listing() {
+        assert_eq!(error_kind(&["--confirm-threshold", "10", "--max-buffered-jobs", "100"]), Some(clap::error::ErrorKind::ArgumentConflict));
+        assert_eq!(error_kind(&["--confirm-threshold", "10", "--dry-run"]), None);
+    }
+
+    #[test]
+    fn parse_status_limit_reads_the_status_and_the_limit() {
+        assert_eq!(parse_status_limit("failed=500"), Ok(("failed".to_string(), 500)));
+        assert_eq!(parse_status_limit(" success = 3 "), Ok(("success".to_string(), 3)));
+        assert!(parse_status_limit("failed").is_err());
+        assert!(parse_status_limit("failed=many").is_err());
+    }
+
+    #[test]
+    fn the_server_time_is_not_pretended() {
+        assert_eq!(error_kind(&["--use-server-time", "--pretend-now", "2024-01-01T00:00:00Z"]), Some(clap::error::ErrorKind::ArgumentConflict));
+        assert_eq!(error_kind(&["--pretend-now", "yesterday"]), Some(clap::error::ErrorKind::ValueValidation));
+    }
+
+    #[test]
+    fn the_job_id_window_is_ordered() {
+        assert_eq!(error_kind(&["--min-job-id", "9", "--max-job-id", "1"]), Some(clap::error::ErrorKind::ArgumentConflict));
+        assert_eq!(error_kind(&["--min-job-id", "1", "--max-job-id", "1"]), None);
+    }
+
+    #[test]
+    fn clock_is_skewed_from_the_pretended_date() {
+        let args = try_parse(&["-p", "project", "--pretend-now", "2024-01-01T00:00:00Z", "--clock-skew-secs", "-60"]).unwrap();
+        assert_eq!(args.clock(args.base_clock()).now(), parse_date("2023-12-31T23:59:00Z").unwrap());
+    }
+
+    #[test]
+    fn parse_interval_reads_the_unit() {
+        assert_eq!(parse_interval("45"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_interval("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_interval("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_interval("6h"), Ok(Duration::from_secs(6 * 60 * 60)));
+        assert_eq!(parse_interval("2d"), Ok(Duration::from_secs(2 * 24 * 60 * 60)));
+        assert!(parse_interval("").is_err());
+        assert!(parse_interval("3w").is_err());
+        assert!(parse_interval("h").is_err());
+    }
+
+    #[test]
+    fn parse_interval_rejects_an_overflow() {
+        assert_eq!(parse_interval("99999999999999999d"), Err("Invalid interval '99999999999999999d', too large.".to_string()));
+        assert!(parse_interval("99999999999999999999").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::{sync::Arc, time::Instant};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::future::BoxFuture;
+
+/// Source of the current date, so that the cutoff and the age filters can be computed from a fixed date,
+/// and of the waits, so that the backoffs and the pauses can be driven without waiting.
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    /// The current date.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// The current instant, measuring the pauses and the timeouts. It follows the waits rather than the date,
+    /// so that a fixed or shifted date never stalls nor skips a pause.
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Waits for the duration, on the timer of the runtime by default.
+    fn sleep(&self, duration: std::time::Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Clock giving the date of the system.
+#[derive(Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock always giving the same date.
+#[derive(Debug)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// Clock shifted from another one, e.g. to make up for the skew of the local clock.
+#[derive(Debug)]
+pub struct SkewedClock {
+    /// The clock shifted.
+    pub clock: Arc<dyn Clock>,
+    /// The shift added to the dates of the clock.
+    pub skew: Duration,
+}
+
+impl Clock for SkewedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now() + self.skew
+    }
+
+    fn instant(&self) -> Instant {
+        self.clock.instant()
+    }
+
+    fn sleep(&self, duration: std::time::Duration) -> BoxFuture<'static, ()> {
+        self.clock.sleep(duration)
+    }
+}
+
+/// Clock whose date only moves when waited on, recording the waits once awaited, so that the backoffs run without
+/// waiting.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct MockClock {
+    /// The current date, advanced by each wait.
+    pub now: std::sync::Arc<std::sync::Mutex<DateTime<Utc>>>,
+    /// The instant the clock was created at, the current instant being advanced from it by each wait.
+    pub start: Instant,
+    /// The waits requested so far.
+    pub sleeps: std::sync::Arc<std::sync::Mutex<Vec<std::time::Duration>>>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        MockClock { now: std::sync::Arc::new(std::sync::Mutex::new(now)), start: Instant::now(), sleeps: std::sync::Arc::default() }
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    fn instant(&self) -> Instant {
+        self.start + self.sleeps.lock().unwrap().iter().sum::<std::time::Duration>()
+    }
+
+    fn sleep(&self, duration: std::time::Duration) -> BoxFuture<'static, ()> {
+        let (now, sleeps) = (self.now.clone(), self.sleeps.clone());
+        Box::pin(async move {
+            *now.lock().unwrap() += Duration::from_std(duration).unwrap();
+            sleeps.lock().unwrap().push(duration);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn fixed_clock_always_gives_the_same_date() {
+        let clock = FixedClock(date("2024-01-01T00:00:00Z"));
+        assert_eq!(clock.now(), date("2024-01-01T00:00:00Z"));
+        assert_eq!(clock.now(), clock.now());
+    }
+
+    #[test]
+    fn skewed_clock_shifts_the_cutoff() {
+        let clock = SkewedClock { clock: Arc::new(FixedClock(date("2024-01-31T00:00:00Z"))), skew: Duration::seconds(-90) };
+        assert_eq!(clock.now(), date("2024-01-30T23:58:30Z"));
+        assert_eq!(clock.now() - Duration::days(30), date("2023-12-31T23:58:30Z"));
+    }
+
+    #[tokio::test]
+    async fn mock_clock_advances_when_waited_on() {
+        let mock = Arc::new(MockClock::new(date("2024-01-01T00:00:00Z")));
+        let clock = SkewedClock { clock: mock.clone(), skew: Duration::hours(1) };
+        clock.sleep(std::time::Duration::from_secs(30)).await;
+        assert_eq!(clock.now(), date("2024-01-01T01:00:30Z"));
+        assert_eq!(*mock.sleeps.lock().unwrap(), vec![std::time::Duration::from_secs(30)]);
+        assert_eq!(clock.instant() - mock.start, std::time::Duration::from_secs(30));
+    }
+
+    #[test]
+    fn fixed_clock_instant_follows_the_real_time() {
+        let clock = FixedClock(date("2024-01-01T00:00:00Z"));
+        let before = clock.instant();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(clock.instant() > before);
+    }
+}
@@ -1,53 +1,219 @@
-use std::{fmt::{Display, self, Formatter}, io::Error};
+use std::{cell::Cell, cmp::Reverse, collections::{BTreeMap, BTreeSet, HashMap, HashSet}, future::Future, ops::Add, io::{Error, ErrorKind, IsTerminal, Write}, path::Path};
 
 use chrono::{Utc, DateTime};
-use clap::{Parser, ValueEnum};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 
 mod actors;
-use actors::{displ::Displ, git::{Git, GetProject, GetJobs, Job}, event::Event};
+mod cli;
+mod clock;
+mod select;
+#[cfg(test)]
+mod mock;
+use clock::Clock;
+use cli::{parse_date, Args, DryRunGroup, ProbeMode, Target};
+use actors::{displ::{Displ, DisplConfig, OutputFormat}, git::{AgeField, CheckEndpoint, CountJobs, GetPause, Deployment, EraseOutcome, Git, GitConfig, GitPool, GetDeployments, GetProject, GetProjectById, GetJob, GetServerTime, GetJobs, GetMetrics, JOBS_PER_PAGE, GetPackages, GetPipelines, GetUser, GetVersion, Job, ListGroupProjects, Package, Pipeline, PostWebhook, ProbeJob, Project, Resource, SearchProjects}, event::Event};
 use tiny_tokio_actor::{EventBus, ActorSystem, ActorRef};
 
-/// Enum used to define the target component(s) of the project to clean.
-#[derive(Parser, Debug, Clone, ValueEnum)]
-enum Target {
-    /// The target is the jobs of the project.
-    Jobs,
+/// Summary of the cleaning of a target.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CleanSummary {
+    /// The number of items that matched the filters.
+    matched: u64,
+    /// The number of items that were erased.
+    erased: u64,
+    /// The number of items that were skipped, e.g. already gone when erased.
+    skipped: u64,
+    /// The number of items that could not be erased.
+    failed: u64,
+    /// The ids of the items that matched the filters.
+    candidates: Vec<u64>,
+    /// The ids of the items that were erased.
+    erased_ids: Vec<u64>,
+    /// Whether items of the window were left out by --limit, --max-pages, --page-end or --per-status-limit.
+    #[serde(skip)]
+    truncated: bool,
 }
 
-impl Display for Target {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            Target::Jobs => write!(f, "jobs"),
+/// Merges the summaries of several targets or projects.
+impl Add for CleanSummary {
+    type Output = CleanSummary;
+
+    fn add(mut self, mut other: CleanSummary) -> CleanSummary {
+        self.candidates.append(&mut other.candidates);
+        self.erased_ids.append(&mut other.erased_ids);
+        CleanSummary {
+            matched: self.matched + other.matched,
+            erased: self.erased + other.erased,
+            skipped: self.skipped + other.skipped,
+            failed: self.failed + other.failed,
+            candidates: self.candidates,
+            erased_ids: self.erased_ids,
+            truncated: self.truncated || other.truncated,
+        }
+    }
+}
+
+impl CleanSummary {
+    /// The exit code of the run: 1 when some items failed, 2 when nothing matched and
+    /// `fail_on_empty` is set, 0 otherwise.
+    fn exit_code(&self, fail_on_empty: bool) -> i32 {
+        if self.failed > 0 {
+            1
+        } else if fail_on_empty && self.matched == 0 {
+            2
+        } else {
+            0
         }
     }
 }
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
+/// Read the cutoff of the last successful run from the state file, if any.
+fn read_last_run(path: &Path) -> Result<Option<DateTime<Utc>>, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => parse_date(content.trim())
+            .map(Some)
+            .map_err(|err| Error::new(std::io::ErrorKind::InvalidData, format!("Invalid state file {}: {}", path.display(), err))),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Write the cutoff of a successful run to the state file, the next run resuming from it.
+fn write_last_run(path: &Path, cutoff: DateTime<Utc>) -> Result<(), Error> {
+    std::fs::write(path, cutoff.to_rfc3339())
+}
+
+/// The ids of the candidates of each target.
+type Snapshot = BTreeMap<String, Vec<u64>>;
+
+/// Report of a run, written with `--summary-out`.
+#[derive(Debug, Serialize, Deserialize)]
+struct SummaryReport {
+    /// The date the run started at.
+    started_at: DateTime<Utc>,
+    /// The date the run finished at.
+    finished_at: DateTime<Utc>,
+    /// The summary of each cleaned project.
+    projects: Vec<ProjectReport>,
+    /// The summary of all the projects.
+    total: CleanSummary,
+}
+
+/// Summary of the cleaning of a project.
+#[derive(Debug, Serialize, Deserialize)]
+struct ProjectReport {
+    /// The name of the project.
+    name: String,
+    /// The id of the project.
+    id: u64,
+    /// The summary of all the targets of the project.
+    summary: CleanSummary,
+}
+
+/// Write the report of the run as JSON.
+fn write_summary(path: &Path, report: &SummaryReport) -> Result<(), Error> {
+    std::fs::write(path, serde_json::to_string_pretty(report)?)
+}
+
+/// Read a snapshot of candidates written by a previous run.
+fn read_snapshot(path: &Path) -> Result<Snapshot, Error> {
+    let content = std::fs::read_to_string(path)?;
+    serde_json::from_str(&content)
+        .map_err(|err| Error::new(std::io::ErrorKind::InvalidData, format!("Invalid snapshot {}: {}", path.display(), err)))
+}
+
+/// Write a snapshot of candidates for a later comparison.
+fn write_snapshot(path: &Path, snapshot: &Snapshot) -> Result<(), Error> {
+    std::fs::write(path, serde_json::to_string_pretty(snapshot)?)
+}
+
+/// The columns of the export of the candidate jobs as CSV.
+const EXPORT_CSV_HEADER: &str = "project_id,id,name,status,ref,created_at,finished_at,erased_at,artifacts_expire_at,pipeline_id,pipeline_status,artifacts_size";
 
-    /// The name of the project to search for.
-    #[arg(short, long)]
-    project: String,
+/// Whether the export of the candidate jobs is written as CSV rather than JSON, from the extension of its file.
+fn export_is_csv(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+}
 
-    /// The group of the project to search for.
-    #[arg(short, long)]
-    group: Option<String>,
+/// Start the export of the candidate jobs, replacing a previous export so that it only records the current run.
+/// The JSON array is opened here and closed by `finish_export`, the jobs being appended in between.
+fn start_export(path: &Path) -> Result<(), Error> {
+    std::fs::write(path, if export_is_csv(path) { format!("{}\n", EXPORT_CSV_HEADER) } else { "[".to_string() })
+}
 
-    /// The target component(s) of the project to clean.
-    #[clap(value_enum)]
-    #[arg(short, long, default_value = "jobs")]
-    target: Target, 
+/// Finish the export of the candidate jobs, closing the JSON array.
+fn finish_export(path: &Path) -> Result<(), Error> {
+    if !export_is_csv(path) {
+        let mut file = std::fs::OpenOptions::new().append(true).open(path)?;
+        writeln!(file, "\n]")?;
+    }
+    Ok(())
+}
 
-    /// The expiration date of the component(s) to clean.
-    #[arg(value_parser = parse_duration, default_value = "365")]
-    expiration_in_days: std::time::Duration,
+/// Append the candidate jobs of a project to the export, before they are erased.
+fn export_candidates(path: &Path, project_id: u64, jobs: &[Job]) -> Result<(), Error> {
+    let mut file = std::fs::OpenOptions::new().append(true).open(path)?;
+    if export_is_csv(path) {
+        let date = |date: Option<DateTime<Utc>>| date.map(|date| date.to_rfc3339()).unwrap_or_default();
+        for job in jobs {
+            let fields = [
+                project_id.to_string(),
+                job.id.to_string(),
+                job.name.clone(),
+                job.status.clone(),
+                job.git_ref.clone(),
+                job.created_at.to_rfc3339(),
+                date(job.finished_at),
+                date(job.erased_at),
+                date(job.artifacts_expire_at),
+                job.pipeline.as_ref().map(|pipeline| pipeline.id.to_string()).unwrap_or_default(),
+                job.pipeline.as_ref().map(|pipeline| pipeline.status.clone()).unwrap_or_default(),
+                job.artifacts_size().to_string(),
+            ];
+            writeln!(file, "{}", fields.map(|field| csv_field(&field)).join(","))?;
+        }
+        Ok(())
+    } else {
+        // The jobs are appended to the array opened by `start_export`, one per line, only the first one not being
+        // preceded by a separator.
+        for job in jobs {
+            let mut value = serde_json::to_value(job)?;
+            value["project_id"] = project_id.into();
+            let separator = if file.metadata()?.len() > 1 { "," } else { "" };
+            write!(file, "{}\n  {}", separator, serde_json::to_string(&value)?)?;
+        }
+        Ok(())
+    }
 }
 
-/// Parse a duration from a days count.
-fn parse_duration(arg: &str) -> Result<std::time::Duration, std::num::ParseIntError> {
-    let days : u64 = arg.parse()?;
-    Ok(std::time::Duration::from_secs(60 * 60 * 24 * days))
+/// Quote a CSV field when it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The key of the candidates of a target in a snapshot, prefixed with the id of the project when several projects
+/// are cleaned, as several projects of a group may share a name.
+fn snapshot_key(multi_project: bool, project_id: u64, target: &Target) -> String {
+    if multi_project { format!("{}/{}", project_id, target) } else { target.to_string() }
+}
+
+/// The ids added and removed for each target between a previous and a current snapshot.
+fn diff_snapshots(previous: &Snapshot, current: &Snapshot) -> BTreeMap<String, (Vec<u64>, Vec<u64>)> {
+    let empty = Vec::new();
+    previous.keys().chain(current.keys())
+        .map(|target| {
+            let before: BTreeSet<&u64> = previous.get(target).unwrap_or(&empty).iter().collect();
+            let after: BTreeSet<&u64> = current.get(target).unwrap_or(&empty).iter().collect();
+            let added = after.difference(&before).map(|id| **id).collect();
+            let removed = before.difference(&after).map(|id| **id).collect();
+            (target.clone(), (added, removed))
+        })
+        .collect()
 }
 
 #[tokio::main]
@@ -57,90 +223,2310 @@ async fn main() {
     let system = ActorSystem::new("gitlab-cleaner", bus);
 
     // Getting the arguments from the CLI parser
-    let args = Args::parse();
-    let project_name = args.project;
-    let project_group = args.group;
-    let expiration_date = chrono::Utc::now() - args.expiration_in_days;
-
-    let displ = Displ::default();
-    let git = Git::default();
-    let git_ref = system.create_actor("git-actor", git).await.unwrap();
+    let mut args = cli::parse();
+
+    // The subscribers are awaited on shutdown, so that the last events are written before exiting.
+    let mut subscribers = Vec::new();
+    if args.log_json {
+        subscribers.push(tokio::spawn(actors::event::log_json(system.events())));
+    }
+    // With the ids printed on stdout, the events go to stderr like the other messages.
+    if args.output == OutputFormat::Ndjson && args.print_ids {
+        subscribers.push(tokio::spawn(actors::event::write_ndjson(system.events(), std::io::stderr())));
+    } else if args.output == OutputFormat::Ndjson {
+        subscribers.push(tokio::spawn(actors::event::write_ndjson(system.events(), std::io::stdout())));
+    }
+
+    // The clock is shared by the actors and the run, so that all the dates and the waits follow the same clock.
+    let base_clock = args.base_clock();
+    let displ = Displ::new(DisplConfig {
+        colors: args.color.enabled(),
+        output: args.output.clone(),
+        summary_only: args.summary_only,
+        refresh_hz: args.progress_refresh_hz,
+        to_stderr: args.print_ids,
+        buffered: args.report_only_failures,
+        clock: base_clock.clone(),
+        captured: None,
+    });
+    let git = match Git::new(GitConfig {
+        base_url: args.gitlab_url.clone().unwrap_or_default(),
+        headers: args.headers.clone(),
+        insecure: args.insecure,
+        ca_cert: args.ca_cert.clone(),
+        dump_requests: args.dump_requests,
+        connect_retries: args.connect_retries,
+        rate_limit_threshold: args.pause_on_rate_limit_threshold,
+        timeout: args.timeout_secs.map(std::time::Duration::from_secs),
+        erase_timeout: args.erase_timeout_secs.map(std::time::Duration::from_secs),
+        backoff: args.backoff(),
+        pool_max_idle_per_host: args.concurrency,
+        token_env: Some(args.token_env.clone()),
+        api_path: Some(args.api_path.clone()),
+        project_cache_ttl: args.project_cache_ttl_secs.map(std::time::Duration::from_secs),
+        clock: Some(base_clock.clone()),
+    }) {
+        Ok(git) => git,
+        // A malformed url or a missing token is reported without a panic.
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let token_trimmed = git.token_trimmed;
+    let git_ref = GitPool::create(&system, "git-actor", git, args.git_actors()).await.unwrap();
     let displ_ref = system.create_actor("displ-actor", displ).await.unwrap();
 
+    if token_trimmed {
+        let _ = displ_ref.ask(actors::displ::DisplayWarning {
+            message: format!("the token read from {} had leading or trailing whitespace, which was trimmed.", args.token_env)
+        }).await;
+    }
+
+    if args.insecure {
+        let _ = displ_ref.ask(actors::displ::DisplayWarning {
+            message: "--insecure is set, the certificates of the Gitlab instance are NOT verified.".to_string()
+        }).await;
+    }
+
+    // The skew of the local clock is measured once, so that all the dates of the run are shifted alike.
+    if args.use_server_time {
+        if let Some(skew) = server_clock_skew(&git_ref, &displ_ref, &*base_clock).await {
+            args.clock_skew_secs = skew;
+        }
+    }
+    let args = args;
+    let clock = args.clock(base_clock);
+    if args.health_check {
+        let healthy = health_check(&git_ref, &displ_ref, &args).await;
+        let _ = displ_ref.ask(actors::displ::FlushMessages { print: !healthy }).await;
+        std::process::exit(if healthy { 0 } else { 1 });
+    }
+
+    let exit_code = match args.interval {
+        Some(interval) => run_loop(&git_ref, &displ_ref, &args, &*clock, interval, stop_on_ctrl_c()).await,
+        None => run(&git_ref, &displ_ref, &args, &*clock).await,
+    };
+    shutdown(system, git_ref, displ_ref, subscribers).await;
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+
+}
+
+/// Measure the seconds between the date of the Gitlab instance and the local clock, warning when the instance did not
+/// send its date.
+async fn server_clock_skew(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>, base_clock: &dyn Clock) -> Option<i64> {
+    let server_now = git_ref.ask(GetServerTime).await
+        .map_err(|err| Error::other(err.to_string()))
+        .and_then(|res| res);
+    match server_now {
+        Ok(server_now) => Some((server_now - base_clock.now()).num_seconds()),
+        Err(err) => {
+            let _ = displ_ref.ask(actors::displ::DisplayWarning {
+                message: format!("could not read the date of the Gitlab instance ({}), using the local clock.", err)
+            }).await;
+            None
+        }
+    }
+}
+
+/// Run the cleanup of the projects once, from the resolution of the window to the final report. Returns the exit code.
+async fn run(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>, args: &Args, clock: &dyn Clock) -> i32 {
+    let started_at = std::time::Instant::now();
+    let started_on = clock.now();
+    // The absolute dates, when given, define the window instead of the expiration in days.
+    let (created_before, mut created_after) = if args.created_before.is_some() || args.created_after.is_some() {
+        (args.created_before, args.created_after)
+    } else {
+        (Some(clock.now() - args.expiration_in_days), None)
+    };
+    // Without a previous run recorded, everything up to the cutoff is considered. The previous cutoff is not before the
+    // current one when the expiration was lengthened meanwhile, nothing having then expired since.
+    if args.since_last_run {
+        let last_run = match args.state_file.as_deref().map(read_last_run).transpose() {
+            Ok(last_run) => last_run.flatten(),
+            Err(err) => exit_on_error(displ_ref, err).await,
+        };
+        if let Some(last_cutoff) = last_run {
+            if created_before.is_some_and(|cutoff| last_cutoff >= cutoff) {
+                let _ = displ_ref.ask(actors::displ::DisplayWarning {
+                    message: format!("the cutoff of the last run ({}) is not before the current one, nothing expired since.", last_cutoff.to_rfc3339())
+                }).await;
+                let _ = displ_ref.ask(actors::displ::ReportWarnings).await;
+                let _ = displ_ref.ask(actors::displ::FlushMessages { print: false }).await;
+                return 0;
+            }
+            created_after = Some(last_cutoff);
+        }
+    }
+
+    let _ = displ_ref.ask(actors::displ::DisplayMessage {
+        message: format!("Cleaning {} {}.", args.target.iter().map(|target| target.to_string()).collect::<Vec<String>>().join(", "), describe_window(created_before, created_after))
+    }).await;
+
+    if !args.skip_version_check {
+        for target in &args.target {
+            check_version(git_ref, displ_ref, target).await;
+        }
+    }
+
+    let mut targets: Vec<Target> = Vec::new();
+    for target in &args.target {
+        if !targets.contains(target) {
+            targets.push(target.clone());
+        }
+    }
+    let projects: Vec<(String, u64)> = if args.all_group_projects {
+        list_group_projects(git_ref, displ_ref, args, clock).await
+    } else {
+        let mut projects = Vec::new();
+        for project_name in args.project_names() {
+            projects.push((project_name.clone(), resolve_project(git_ref, displ_ref, project_name, args).await));
+        }
+        for project_id in args.project_ids() {
+            projects.push((resolve_project_id(git_ref, displ_ref, project_id).await, project_id));
+        }
+        projects
+    };
+    let multi_project = projects.len() > 1 || args.all_group_projects;
+    let plan = match args.apply_file.as_deref().map(read_snapshot).transpose() {
+        Ok(plan) => plan,
+        Err(err) => exit_on_error(displ_ref, err).await,
+    };
+    // A plan lacking a project was written for other projects, so nothing is erased rather than a part of it.
+    if let (Some(plan), Some(path)) = (&plan, &args.apply_file) {
+        let missing: Vec<String> = projects.iter()
+            .map(|(_, project_id)| snapshot_key(multi_project, *project_id, &Target::Jobs))
+            .filter(|key| !plan.contains_key(key))
+            .collect();
+        if !missing.is_empty() {
+            exit_on_error(displ_ref, Error::new(ErrorKind::InvalidData, format!(
+                "The plan {} has no entry for {}, it was written for other projects.", path.display(), missing.join(", ")))).await;
+        }
+    }
+    if let Some(path) = &args.export_candidates {
+        if let Err(err) = start_export(path) {
+            exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not start the export {}: {}", path.display(), err))).await;
+        }
+    }
+    let mut snapshot = Snapshot::new();
+    let mut summary = CleanSummary::default();
+    let mut project_reports: Vec<ProjectReport> = Vec::new();
+    if multi_project {
+        let _ = displ_ref.ask(actors::displ::InitProjectsProgress { length: projects.len() as u64 }).await;
+    }
+    for (project_name, project_id) in &projects {
+        let project_id = *project_id;
+
+        if let Some(job_id) = args.inspect_job {
+            let job = git_ref.ask(GetJob { project_id, job_id }).await
+                .map_err(|err| Error::other(err.to_string()))
+                .and_then(|res| res)
+                .and_then(|job| serde_json::to_string_pretty(&job).map_err(Error::from));
+            match job {
+                Ok(job) => println!("{}", job),
+                Err(err) => exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not inspect the job {}: {}", job_id, err))).await,
+            }
+            return 0;
+        }
+
+        let summaries = match &plan {
+            // The plan is keyed as the snapshot it was written as, and replaces the listing and the filtering of the jobs.
+            Some(plan) => {
+                let job_ids = &plan[&snapshot_key(multi_project, project_id, &Target::Jobs)];
+                vec![apply_plan(git_ref, displ_ref, project_id, job_ids, args, clock).await]
+            }
+            None => clean_project(git_ref, displ_ref, project_id, &targets, created_before, created_after, args, clock).await,
+        };
+        for (target, target_summary) in targets.iter().zip(&summaries) {
+            snapshot.insert(snapshot_key(multi_project, project_id, target), target_summary.candidates.clone());
+        }
+        let project_summary = summaries.into_iter().fold(CleanSummary::default(), |total, summary| total + summary);
+        if multi_project {
+            let _ = displ_ref.ask(actors::displ::DisplayMessage {
+                message: format!("Project {}: {} erased, {} skipped, {} failed.", project_name, project_summary.erased, project_summary.skipped, project_summary.failed)
+            }).await;
+        }
+        project_reports.push(ProjectReport { name: project_name.clone(), id: project_id, summary: project_summary.clone() });
+        summary = summary + project_summary;
+        let _ = displ_ref.ask(actors::displ::IncreaseProjectsProgress).await;
+    }
+    if let Some(path) = &args.export_candidates {
+        if let Err(err) = finish_export(path) {
+            exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not finish the export {}: {}", path.display(), err))).await;
+        }
+    }
+    if multi_project {
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: format!("Total of {} projects: {} erased, {} skipped, {} failed.", projects.len(), summary.erased, summary.skipped, summary.failed)
+        }).await;
+    }
+    if let Some(path) = &args.snapshot_in {
+        let previous = match read_snapshot(path) {
+            Ok(previous) => previous,
+            Err(err) => exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not read the snapshot {}: {}", path.display(), err))).await,
+        };
+        for (target, (added, removed)) in diff_snapshots(&previous, &snapshot) {
+            let _ = displ_ref.ask(actors::displ::DisplayMessage {
+                message: format!("Since the snapshot, {}: {} added {:?}, {} removed {:?}.", target, added.len(), added, removed.len(), removed)
+            }).await;
+        }
+    }
+    if let Some(path) = &args.snapshot_out {
+        if let Err(err) = write_snapshot(path, &snapshot) {
+            exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not write the snapshot {}: {}", path.display(), err))).await;
+        }
+    }
+
+    if args.print_ids {
+        for id in &summary.erased_ids {
+            println!("{}", id);
+        }
+    }
+
+    if args.metrics {
+        let metrics = git_ref.ask(GetMetrics).await.unwrap_or_default();
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: format!("Metrics: {} requests, {:.0?} average latency, {:.0?} p95 latency, {} bytes downloaded, {:.1?} elapsed.",
+                metrics.count(), metrics.average(), metrics.percentile(0.95), metrics.bytes, started_at.elapsed())
+        }).await;
+    }
+
+    let exit_code = summary.exit_code(args.fail_on_empty);
+    let report = SummaryReport {
+        started_at: started_on,
+        finished_at: clock.now(),
+        projects: project_reports,
+        total: summary.clone(),
+    };
+    if let Some(path) = &args.summary_out {
+        if let Err(err) = write_summary(path, &report) {
+            exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not write the summary {}: {}", path.display(), err))).await;
+        }
+    }
+    if let Some(url) = &args.webhook_url {
+        let payload = if args.webhook_slack {
+            serde_json::json!({ "text": format!("Gitlab cleaning of {} project(s) {}: {} erased, {} skipped, {} failed.",
+                report.projects.len(), if exit_code == 0 { "succeeded" } else { "failed" },
+                summary.erased, summary.skipped, summary.failed) })
+        } else {
+            serde_json::to_value(&report).unwrap_or_default()
+        };
+        let posted = git_ref.ask(PostWebhook { url: url.clone(), payload }).await
+            .map_err(|err| Error::other(err.to_string()))
+            .and_then(|res| res);
+        if let Err(err) = posted {
+            let _ = displ_ref.ask(actors::displ::DisplayMessage {
+                message: format!("Could not post the summary to the webhook: {}", err)
+            }).await;
+        }
+    }
+
+    // The items left out would not be considered again by the next run, resumed after the cutoff.
+    if summary.truncated && args.state_file.is_some() && !args.dry_run {
+        let _ = displ_ref.ask(actors::displ::DisplayWarning {
+            message: "the state file is not updated, the run was cut short by --limit, --max-pages, --page-end or --per-status-limit.".to_string()
+        }).await;
+    }
+    let _ = displ_ref.ask(actors::displ::ReportWarnings).await;
+    // The messages held back are only reported when the run did not succeed.
+    let _ = displ_ref.ask(actors::displ::FlushMessages { print: exit_code != 0 }).await;
+    if exit_code == 0 && !args.dry_run && !summary.truncated {
+        if let Some(path) = &args.state_file {
+            if let Err(err) = write_last_run(path, created_before.unwrap_or(started_on)) {
+                exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not write the state file {}: {}", path.display(), err))).await;
+            }
+        }
+    }
+    exit_code
+}
+
+/// The notification of a first Ctrl-C, a second one exiting at once.
+fn stop_on_ctrl_c() -> std::sync::Arc<tokio::sync::Notify> {
+    let stop = std::sync::Arc::new(tokio::sync::Notify::new());
+    let stopping = stop.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            stopping.notify_one();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+    stop
+}
+
+/// Run the cleanup in cycles separated by the interval until notified to stop, which stops the loop once the current
+/// cycle is over. Returns the exit code of the last cycle.
+async fn run_loop(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    args: &Args,
+    clock: &dyn Clock,
+    interval: std::time::Duration,
+    stop: std::sync::Arc<tokio::sync::Notify>) -> i32 {
+    let mut cycle = 1;
+    loop {
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: format!("Cycle {}:", cycle)
+        }).await;
+        let exit_code = run(git_ref, displ_ref, args, clock).await;
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: format!("Cycle {} done, next one in {}s. Press Ctrl-C to stop.", cycle, interval.as_secs())
+        }).await;
+        // A Ctrl-C received during the cycle is kept by the notification until waited for, and stops the loop
+        // before the interval is waited.
+        tokio::select! {
+            biased;
+            _ = stop.notified() => {
+                let _ = displ_ref.ask(actors::displ::DisplayMessage {
+                    message: "Stopped.".to_string()
+                }).await;
+                return exit_code;
+            }
+            _ = clock.sleep(interval) => cycle += 1,
+        }
+    }
+}
+
+/// Stop the actors once they processed their pending messages, then wait for the subscribers of the event bus to
+/// write the last events, so that no task is left running.
+async fn shutdown(system: ActorSystem<Event>, git_ref: GitPool, displ_ref: ActorRef<Event, Displ>, subscribers: Vec<tokio::task::JoinHandle<()>>) {
+    // A message is answered once the previous ones are processed, so asking one drains the mailbox.
+    for actor in &git_ref.actors {
+        let _ = actor.ask(GetPause).await;
+    }
+    let _ = displ_ref.ask(actors::displ::StopSpinner).await;
+    let mut paths = git_ref.paths();
+    paths.push(displ_ref.path().clone());
+    // The actors stop once their last reference, held by the system, is dropped.
+    drop((git_ref, displ_ref));
+    for path in &paths {
+        system.stop_actor(path).await;
+    }
+    // The subscribers end once the bus is closed, with the system and the contexts of the stopped actors.
+    drop(system);
+    for subscriber in subscribers {
+        let _ = subscriber.await;
+    }
+}
+
+/// Resolve the id of a project from its name and the group, displaying a spinner meanwhile.
+async fn resolve_project(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>, project_name: &str, args: &Args) -> u64 {
     let get_project_message = GetProject {
-        project_name: project_name.clone(),
-        project_group: project_group.clone()
+        project_name: project_name.to_string(),
+        project_group: args.group.clone()
+    };
+
+    let _ = displ_ref.ask(actors::displ::StartSpinner {
+        message: format!("Resolving the project {}...", project_name)
+    }).await;
+    let project_result = if args.interactive {
+        pick_project(git_ref, displ_ref, project_name, args).await
+    } else {
+        git_ref.ask(get_project_message).await
+            .map_err(|err| Error::other(err.to_string()))
+            .and_then(|res| res)
     };
-    
-    // Better unwrap here to panic in case of error.
-    let project_id = git_ref.ask(get_project_message).await
-        .or_else(|err| Err(Error::new(std::io::ErrorKind::Other, err.to_string())))
-        .or_else(|err| Err(Error::new(std::io::ErrorKind::Other, err.to_string())))
-        .unwrap().unwrap();
+    let _ = displ_ref.ask(actors::displ::StopSpinner).await;
+
+    match project_result {
+        Ok(project_id) => project_id,
+        Err(err) => exit_on_error(displ_ref, err).await,
+    }
+}
+
+/// Search the projects matching the name and, when there are several, prompt for the one to clean.
+async fn pick_project(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>, project_name: &str, args: &Args) -> Result<u64, Error> {
+    let projects = git_ref.ask(SearchProjects {
+        project_name: project_name.to_string(),
+        project_group: args.group.clone(),
+        limit: args.project_search_limit
+    }).await
+        .map_err(|err| Error::other(err.to_string()))??;
+    match projects.len() {
+        0 => return Err(Error::new(ErrorKind::NotFound, "No project found that matches the researched term.")),
+        1 => return Ok(projects[0].id),
+        _ => {}
+    }
+    let _ = displ_ref.ask(actors::displ::StopSpinner).await;
+    for (index, project) in projects.iter().enumerate() {
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: format!("{:>3}. {} ({}, id {})", index + 1, project.name, project.path_with_namespace, project.id)
+        }).await;
+    }
+    let answer = ask(displ_ref, format!("Several projects match {}, which one to clean? [1-{}]", project_name, projects.len())).await
+        .unwrap_or_default();
+    parse_selection(&answer, projects.len())
+        .map(|index| projects[index].id)
+        .ok_or(Error::new(ErrorKind::InvalidInput, format!("Invalid selection {:?}: expected a number between 1 and {}.", answer.trim(), projects.len())))
+}
+
+/// Parse the 1-based number of an item picked among `count` ones into its index.
+fn parse_selection(answer: &str, count: usize) -> Option<usize> {
+    answer.trim().parse::<usize>().ok()
+        .filter(|number| (1..=count).contains(number))
+        .map(|number| number - 1)
+}
 
-    match args.target {
-        Target::Jobs => clean_jobs(&git_ref, &displ_ref, project_id, expiration_date).await,
+/// Checks that the project with the given id exists and is accessible, returning its name.
+async fn resolve_project_id(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>, project_id: u64) -> String {
+    let _ = displ_ref.ask(actors::displ::StartSpinner {
+        message: format!("Checking the project {}...", project_id)
+    }).await;
+    let project_result = git_ref.ask(GetProjectById { project_id }).await
+        .map_err(|err| Error::other(err.to_string()))
+        .and_then(|res| res);
+    let _ = displ_ref.ask(actors::displ::StopSpinner).await;
+
+    match project_result {
+        Ok(project) => project.name,
+        Err(err) => exit_on_error(displ_ref, err).await,
     }
+}
+
+/// Report an error the user can act upon, along with the messages held back, and exit with the code 1.
+async fn exit_on_error(displ_ref: &ActorRef<Event, Displ>, err: Error) -> ! {
+    let _ = displ_ref.ask(actors::displ::DisplayMessage { message: err.to_string() }).await;
+    let _ = displ_ref.ask(actors::displ::FlushMessages { print: true }).await;
+    std::process::exit(1);
+}
 
+/// List the projects of the group with one of the requested visibilities, if any, skipping the archived projects
+/// unless asked otherwise and the recently active ones. Returns their names and ids.
+async fn list_group_projects(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>, args: &Args, clock: &dyn Clock) -> Vec<(String, u64)> {
+    let group = args.group.clone().unwrap_or_default();
+    // The page range and the page cap of the listings of the targets do not apply to the projects, all being listed.
+    let mut projects: Vec<Project> = Vec::new();
+    let mut next_page = Some(1);
+    while let Some(page) = next_page {
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: format!("Loading projects from page {}", page)
+        }).await;
+        let mut projects_result = match git_ref.ask(ListGroupProjects { group: group.clone(), page }).await
+            .or(Err(Error::other("Could not send the action to list the projects.")))
+            .and_then(|result| result) {
+            Ok(projects_result) => projects_result,
+            Err(err) => exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not find the projects: {}", err))).await,
+        };
+        projects.append(&mut projects_result.projects);
+        next_page = projects_result.next_page;
+    }
+    let idle_since = clock.now();
+    projects.into_iter()
+        .filter(|project| args.visibility.is_empty() || args.visibility.contains(&project.visibility))
+        .filter(|project| args.include_archived || !project.archived)
+        .filter(|project| match (args.min_project_idle_days, project.last_activity_at) {
+            (Some(days), Some(last_activity)) => last_activity <= idle_since - chrono::Duration::days(days as i64),
+            _ => true,
+        })
+        .map(|project| (project.name, project.id))
+        .collect()
 }
 
+/// Clean the targets of a project, one after the other or concurrently. Returns the summary of each target.
+#[allow(clippy::too_many_arguments)]
+async fn clean_project(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    targets: &[Target],
+    created_before: Option<DateTime<Utc>>,
+    created_after: Option<DateTime<Utc>>,
+    args: &Args,
+    clock: &dyn Clock) -> Vec<CleanSummary> {
+    // With a confirmation, the targets are listed concurrently so that their candidates are confirmed together.
+    let gate = Gate::needed(args).then(|| Gate::new(targets.len()));
+    let target_summaries = targets.iter()
+        .map(|target| clean_target(git_ref, displ_ref, project_id, target, created_before, created_after, args, clock, gate.as_ref()));
+    if args.parallel_targets || gate.is_some() {
+        futures::future::join_all(target_summaries).await
+    } else {
+        let mut summaries = Vec::new();
+        for target_summary in target_summaries {
+            summaries.push(target_summary.await);
+        }
+        summaries
+    }
+}
+
+/// Erase the jobs of a plan reviewed from the snapshot of a previous dry run, without listing nor filtering them.
+/// The jobs already erased or gone since the dry run are skipped.
+async fn apply_plan(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    job_ids: &[u64],
+    args: &Args,
+    clock: &dyn Clock) -> CleanSummary {
+    let _ = displ_ref.ask(actors::displ::DisplayMessage {
+        message: format!("Loading the {} jobs of the plan", job_ids.len())
+    }).await;
+    let mut jobs: Vec<Job> = Vec::new();
+    let mut skipped = 0;
+    for job_id in job_ids.iter().collect::<BTreeSet<&u64>>() {
+        let message = match git_ref.ask(GetJob { project_id, job_id: *job_id }).await.map_err(|err| Error::other(err.to_string())).and_then(|res| res) {
+            Ok(job) if job.erased_at.is_none() => {
+                jobs.push(job);
+                continue;
+            }
+            Ok(_) => format!("the job {} of the plan is already erased, skipped.", job_id),
+            Err(err) if err.kind() == ErrorKind::NotFound => format!("the job {} of the plan is already gone, skipped.", job_id),
+            Err(err) => exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not get the job {} of the plan: {}", job_id, err))).await,
+        };
+        let _ = displ_ref.ask(actors::displ::DisplayWarning { message }).await;
+        skipped += 1;
+    }
+
+    if let Some(path) = &args.export_candidates {
+        if let Err(err) = export_candidates(path, project_id, &jobs) {
+            exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not export the candidates to {}: {}", path.display(), err))).await;
+        }
+    }
+    let gate = Gate::needed(args).then(|| Gate::new(1));
+    let summary = process_all(displ_ref, "jobs", &jobs, args, clock, gate.as_ref(), |job| erase_job(git_ref, displ_ref, project_id, job, args, clock)).await;
+    if let Some(gate) = &gate {
+        gate.leave(displ_ref, args, "jobs").await;
+    }
+    summary + CleanSummary { skipped, ..CleanSummary::default() }
+}
+
+/// Warn the user when the Gitlab instance is older than the minimum version supported by the target.
+async fn check_version(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>, target: &Target) {
+    let minimum = target.minimum_gitlab_version();
+    let message = match git_ref.ask(GetVersion).await.map_err(|err| Error::other(err.to_string())).and_then(|res| res) {
+        Ok(version) if !version.is_at_least(minimum) => format!(
+            "the Gitlab instance runs version {} but cleaning {} requires at least {}.{}. Some endpoints might be missing.",
+            version.version, target, minimum.0, minimum.1),
+        Ok(_) => return,
+        Err(err) => format!("could not detect the Gitlab version ({}). Use --skip-version-check to skip this check.", err),
+    };
+    let _ = displ_ref.ask(actors::displ::DisplayWarning { message }).await;
+}
+
+/// Check the connectivity, the token and the target endpoints of the project without deleting anything,
+/// displaying a checklist. Returns whether all the checks passed.
+async fn health_check(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>, args: &Args) -> bool {
+    let mut checks: Vec<(String, Result<String, Error>)> = Vec::new();
+
+    let version = git_ref.ask(GetVersion).await.map_err(|err| Error::other(err.to_string())).and_then(|res| res);
+    checks.push(("Gitlab API reachable".to_string(), version.map(|version| format!("version {}", version.version))));
+
+    let user = git_ref.ask(GetUser).await.map_err(|err| Error::other(err.to_string())).and_then(|res| res);
+    checks.push(("Token valid".to_string(), user.map(|user| format!("authenticated as {}", user.username))));
+
+    let mut found: Vec<(String, Option<u64>)> = Vec::new();
+    for project_name in args.project_names() {
+        let project = git_ref.ask(GetProject {
+            project_name: project_name.clone(),
+            project_group: args.group.clone()
+        }).await.map_err(|err| Error::other(err.to_string())).and_then(|res| res);
+        found.push((project_name.clone(), project.as_ref().ok().copied()));
+        checks.push((format!("Project {} found", project_name), project.map(|id| format!("id {}", id))));
+    }
+    for project_id in args.project_ids() {
+        let project = git_ref.ask(GetProjectById { project_id }).await
+            .map_err(|err| Error::other(err.to_string()))
+            .and_then(|res| res);
+        found.push((project_id.to_string(), project.as_ref().ok().map(|project| project.id)));
+        checks.push((format!("Project {} found", project_id), project.map(|project| format!("name {}", project.name))));
+    }
+
+    for (project_name, project_id) in found {
+        if let Some(project_id) = project_id {
+            for target in &args.target {
+                let reachable = git_ref.ask(CheckEndpoint { path: format!("/projects/{}/{}", project_id, target.listing()) }).await
+                    .map_err(|err| Error::other(err.to_string()))
+                    .and_then(|res| res);
+                checks.push((format!("Endpoint of the {} of {} reachable", target, project_name), reachable.map(|_| "ok".to_string())));
+            }
+        }
+    }
+
+    let mut healthy = true;
+    for (name, result) in checks {
+        let message = match result {
+            Ok(detail) => format!("[PASS] {} ({})", name, detail),
+            Err(err) => {
+                healthy = false;
+                format!("[FAIL] {}: {}", name, err)
+            }
+        };
+        let _ = displ_ref.ask(actors::displ::DisplayMessage { message }).await;
+    }
+    healthy
+}
+
+/// Clean a single target of the project.
+#[allow(clippy::too_many_arguments)]
+async fn clean_target(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    target: &Target,
+    created_before: Option<DateTime<Utc>>,
+    created_after: Option<DateTime<Utc>>,
+    args: &Args,
+    clock: &dyn Clock,
+    gate: Option<&Gate>) -> CleanSummary {
+    let summary = match target {
+        Target::Jobs => clean_jobs(git_ref, displ_ref, project_id, created_before, created_after, args, clock, gate).await,
+        Target::Pipelines => clean_pipelines(git_ref, displ_ref, project_id, created_before, created_after, args, clock, gate).await,
+        Target::Deployments => clean_deployments(git_ref, displ_ref, project_id, created_before, created_after, args, clock, gate).await,
+        Target::Packages => clean_packages(git_ref, displ_ref, project_id, created_before, created_after, args, clock, gate).await,
+        Target::Artifacts => clean_artifacts(git_ref, displ_ref, project_id, created_before, created_after, args, clock, gate).await,
+    };
+    // A target with nothing to erase, or skipped before erasing, must not hold back the confirmation of the others.
+    if let Some(gate) = gate {
+        gate.leave(displ_ref, args, &target.to_string()).await;
+    }
+    summary
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn clean_jobs(
-    git_ref: &ActorRef<Event, Git>,
+    git_ref: &GitPool,
     displ_ref: &ActorRef<Event, Displ>, 
     project_id: u64, 
-    expiration_date: DateTime<Utc>) -> () {
-    let mut jobs_page = Some(1);
-    let mut full_jobs: Vec<Job> = Vec::new();
-    while let Some(page) = jobs_page {
-        let _ = displ_ref.ask(actors::displ::DisplayMessage {
-            message: format!("Loading jobs from page {}", page)
-        }).await;
+    created_before: Option<DateTime<Utc>>,
+    created_after: Option<DateTime<Utc>>,
+    args: &Args,
+    clock: &dyn Clock,
+    gate: Option<&Gate>) -> CleanSummary {
+    // The total number of jobs of the project, counted from the listed pages without `x-total` header.
+    let total_jobs: Cell<Option<u64>> = Cell::new(None);
+    let listed_jobs: Cell<u64> = Cell::new(0);
+    // The pages are bounded by the maximum of buffered jobs, so that a chunk never exceeds it.
+    let per_page = args.max_buffered_jobs.map_or(JOBS_PER_PAGE, |max| (max as u64).clamp(1, JOBS_PER_PAGE));
+    let fetch_page = |page| {
+        let (total_jobs, listed_jobs) = (&total_jobs, &listed_jobs);
+        async move {
+            let jobs_result = git_ref.ask(GetJobs {
+                project_id,
+                created_before,
+                created_after,
+                age_field: args.age_field.clone(),
+                keyset: args.keyset_pagination,
+                after_id: args.after_id,
+                page,
+                per_page
+            }).await
+                .or(Err(Error::other("Could not send the action to get the jobs.")))??;
+            total_jobs.set(total_jobs.get().or(jobs_result.total));
+            listed_jobs.set(listed_jobs.get() + jobs_result.listed as u64);
+            // A job created before the window may have finished within it, so only the creation date allows to stop early,
+            // the jobs being listed newest first unless resumed after an id.
+            let stop = args.age_field == AgeField::Created && args.after_id.is_none()
+                && past_window(args, created_after, jobs_result.oldest_created_at);
+            let next_page = jobs_result.next_page.filter(|_| !stop);
+            Ok((jobs_result.jobs, next_page))
+        }
+    };
+
+    // With a maximum of buffered jobs, the buffered jobs are erased before fetching the next pages.
+    let mut summary = CleanSummary::default();
+    let mut seen_ids: HashSet<u64> = HashSet::new();
+    let mut selected_per_status: HashMap<String, u64> = HashMap::new();
+    let mut selected: u64 = 0;
+    let mut probed = false;
+    let mut latest_attempts: HashSet<(String, String)> = HashSet::new();
+    let mut kept_per_ref: HashMap<String, u64> = HashMap::new();
+    let mut next_page = Some(args.page_start.unwrap_or(1));
+    let mut first_chunk = true;
+    let mut truncated = false;
+    while let Some(page) = next_page {
+        let (mut full_jobs, chunk_next_page, chunk_truncated) = fetch_pages_from(displ_ref, "jobs", args, page, args.max_buffered_jobs.map(|max| (max, per_page as usize)), &fetch_page).await;
+        next_page = chunk_next_page;
+        truncated |= chunk_truncated;
+        if next_page.is_some() {
+            let _ = displ_ref.ask(actors::displ::DisplayWarning {
+                message: format!("the buffer of {} jobs is full (--max-buffered-jobs), erasing them before fetching the next pages.", full_jobs.len())
+            }).await;
+        }
+
+        if first_chunk {
+            first_chunk = false;
+            if let Some(min_jobs) = args.min_jobs {
+                // Without `x-total` header, the jobs listed so far are enough to reach the minimum, or else all
+                // the jobs are counted since the listing may have stopped early.
+                let total = match total_jobs.get() {
+                    Some(total) => total,
+                    None if listed_jobs.get() >= min_jobs => listed_jobs.get(),
+                    None => git_ref.ask(CountJobs { project_id }).await
+                        .map_err(|err| Error::other(err.to_string()))
+                        .and_then(|res| res)
+                        .unwrap_or(listed_jobs.get()),
+                };
+                if total < min_jobs {
+                    let _ = displ_ref.ask(actors::displ::DisplayMessage {
+                        message: format!("Skipping the jobs cleanup: the project has {} jobs, less than the minimum of {}.", total, min_jobs)
+                    }).await;
+                    return CleanSummary::default();
+                }
+            }
+        }
+
+        // Offset pagination can return the same job on two pages when jobs are created meanwhile.
+        let fetched_count = full_jobs.len();
+        full_jobs.retain(|job| seen_ids.insert(job.id));
+        let duplicates_count = fetched_count - full_jobs.len();
+        if duplicates_count > 0 {
+            let _ = displ_ref.ask(actors::displ::DisplayWarning {
+                message: format!("dropped {} duplicated jobs returned by several pages, shifted by the jobs created meanwhile. Use --keyset-pagination to traverse the jobs by id.", duplicates_count)
+            }).await;
+        }
+
+        // The jobs are listed newest first, so the first attempt seen of a job is its latest one.
+        if args.only_superseded {
+            full_jobs.sort_by_key(|job| Reverse(job.id));
+            full_jobs.retain(|job| !latest_attempts.insert((job.name.clone(), job.git_ref.clone())));
+        }
+        // The newest jobs of each ref are kept over all the chunks, the chunks being listed newest first.
+        if let Some(keep) = args.keep_per_ref {
+            full_jobs.sort_by_key(|job| Reverse((job.created_at, job.id)));
+            full_jobs.retain(|job| {
+                let kept = kept_per_ref.entry(job.git_ref.clone()).or_insert(0);
+                if *kept < keep {
+                    *kept += 1;
+                    return false;
+                }
+                true
+            });
+        }
+
+        let now = clock.now();
+        full_jobs.retain(|job| job_matches(job, args, now));
+        // The limits hold over all the chunks, the jobs beyond the limit of their status being kept.
+        full_jobs.retain(|job| {
+            let Some((_, limit)) = args.per_status_limit.iter().find(|(status, _)| *status == job.status) else {
+                return true;
+            };
+            let count = selected_per_status.entry(job.status.clone()).or_insert(0);
+            *count += 1;
+            truncated |= *count > *limit;
+            *count <= *limit
+        });
+        // The jobs of the statuses with the highest priority are erased first, up to the limit over all the chunks.
+        if let Some(limit) = args.limit {
+            full_jobs.sort_by_key(|job| args.status_priority.iter().position(|status| *status == job.status).unwrap_or(args.status_priority.len()));
+            truncated |= full_jobs.len() as u64 > limit.saturating_sub(selected);
+            full_jobs.truncate(limit.saturating_sub(selected) as usize);
+            selected += full_jobs.len() as u64;
+        }
 
+        if let Some(path) = &args.export_candidates {
+            if let Err(err) = export_candidates(path, project_id, &full_jobs) {
+                exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not export the candidates to {}: {}", path.display(), err))).await;
+            }
+        }
+        summary = summary + process_all(displ_ref, "jobs", &full_jobs, args, clock, gate, |job| erase_job(git_ref, displ_ref, project_id, job, args, clock)).await;
+        if let Some(mode) = args.dry_run_probe.as_ref().filter(|_| args.dry_run && !probed) {
+            // The first jobs listed are the newest ones, so the probe is done once on the first chunk.
+            if let Some(job) = full_jobs.iter().max_by_key(|job| job.created_at) {
+                probed = true;
+                probe_job(git_ref, displ_ref, project_id, job, mode).await;
+            }
+        }
+        if args.limit.is_some_and(|limit| selected >= limit) {
+            truncated |= next_page.is_some();
+            break;
+        }
+    }
+    summary + CleanSummary { truncated, ..CleanSummary::default() }
+}
+
+/// The resolved date window in words, e.g. `created before 2024-03-01T00:00:00Z`.
+fn describe_window(created_before: Option<DateTime<Utc>>, created_after: Option<DateTime<Utc>>) -> String {
+    let format = |date: DateTime<Utc>| date.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    match (created_before, created_after) {
+        (Some(before), Some(after)) => format!("created after {} and before {}", format(after), format(before)),
+        (Some(before), None) => format!("created before {}", format(before)),
+        (None, Some(after)) => format!("created after {}", format(after)),
+        (None, None) => "created at any date".to_string(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn clean_artifacts(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    created_before: Option<DateTime<Utc>>,
+    created_after: Option<DateTime<Utc>>,
+    args: &Args,
+    clock: &dyn Clock,
+    gate: Option<&Gate>) -> CleanSummary {
+    let (mut jobs, truncated): (Vec<Job>, bool) = fetch_pages(displ_ref, "artifacts", args, |page| async move {
         let jobs_result = git_ref.ask(GetJobs {
             project_id,
-            older_than: expiration_date,
+            created_before,
+            created_after,
+            age_field: args.age_field.clone(),
+            keyset: args.keyset_pagination,
+            after_id: args.after_id,
+            page,
+            per_page: JOBS_PER_PAGE
+        }).await
+            .or(Err(Error::other("Could not send the action to get the jobs.")))??;
+        let stop = args.age_field == AgeField::Created && args.after_id.is_none()
+            && past_window(args, created_after, jobs_result.oldest_created_at);
+        let next_page = jobs_result.next_page.filter(|_| !stop);
+        Ok((jobs_result.jobs, next_page))
+    }).await;
+    // The log of a job is listed as a `trace` artifact but is not deleted with the artifacts.
+    jobs.retain(|job| job.artifacts.iter()
+        .any(|artifact| artifact.file_type != "trace" && (args.artifact_type.is_empty() || args.artifact_type.contains(&artifact.file_type))));
+
+    process_all(displ_ref, "artifacts", &jobs, args, clock, gate, |job| delete_job_artifacts(git_ref, displ_ref, project_id, job)).await
+        + CleanSummary { truncated, ..CleanSummary::default() }
+}
+
+/// Whether a job matches the job specific filters, on top of the date window.
+fn job_matches(job: &Job, args: &Args, now: DateTime<Utc>) -> bool {
+    (!args.include_artifacts_expired_only || job.artifacts_expire_at.is_some_and(|expire_at| expire_at < now))
+        && !(args.keep_success && job.status == "success")
+        && (args.status.is_empty() || args.status.contains(&job.status))
+        && !args.exclude_status.contains(&job.status)
+        && (args.by_pipeline_status.is_empty()
+            || job.pipeline.as_ref().is_some_and(|pipeline| args.by_pipeline_status.contains(&pipeline.status)))
+        && (!args.only_with_artifacts || job.artifacts_size() > 0)
+        && args.min_job_id.is_none_or(|min| job.id >= min)
+        && args.max_job_id.is_none_or(|max| job.id <= max)
+        && args.select.as_ref().is_none_or(|selection| selection.matches(job, now))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn clean_pipelines(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    created_before: Option<DateTime<Utc>>,
+    created_after: Option<DateTime<Utc>>,
+    args: &Args,
+    clock: &dyn Clock,
+    gate: Option<&Gate>) -> CleanSummary {
+    let (mut pipelines, truncated): (Vec<Pipeline>, bool) = fetch_pages(displ_ref, "pipelines", args, |page| async move {
+        let pipelines_result = git_ref.ask(GetPipelines {
+            project_id,
+            created_before,
+            created_after,
+            statuses: args.pipeline_status.clone(),
+            page
+        }).await
+            .or(Err(Error::other("Could not send the action to get the pipelines.")))??;
+        let next_page = pipelines_result.next_page.filter(|_| !past_window(args, created_after, pipelines_result.oldest_created_at));
+        Ok((pipelines_result.pipelines, next_page))
+    }).await;
+    if args.keep_success {
+        pipelines.retain(|pipeline| pipeline.status != "success");
+    }
+
+    process_all(displ_ref, "pipelines", &pipelines, args, clock, gate, |pipeline| delete_pipeline(git_ref, displ_ref, project_id, pipeline)).await
+        + CleanSummary { truncated, ..CleanSummary::default() }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn clean_deployments(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    created_before: Option<DateTime<Utc>>,
+    created_after: Option<DateTime<Utc>>,
+    args: &Args,
+    clock: &dyn Clock,
+    gate: Option<&Gate>) -> CleanSummary {
+    let environment = args.environment.clone().unwrap_or_default();
+    let (deployments, truncated): (Vec<Deployment>, bool) = fetch_pages(displ_ref, "deployments", args, |page| {
+        let environment = environment.clone();
+        async move {
+            let deployments_result = git_ref.ask(GetDeployments {
+                project_id,
+                environment,
+                created_before,
+                created_after,
+                page
+            }).await
+                .or(Err(Error::other("Could not send the action to get the deployments.")))??;
+            Ok((deployments_result.deployments, deployments_result.next_page))
+        }
+    }).await;
+
+    process_all(displ_ref, "deployments", &deployments, args, clock, gate, |deployment| delete_deployment(git_ref, displ_ref, project_id, deployment)).await
+        + CleanSummary { truncated, ..CleanSummary::default() }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn clean_packages(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    created_before: Option<DateTime<Utc>>,
+    created_after: Option<DateTime<Utc>>,
+    args: &Args,
+    clock: &dyn Clock,
+    gate: Option<&Gate>) -> CleanSummary {
+    let (packages, truncated): (Vec<Package>, bool) = fetch_pages(displ_ref, "packages", args, |page| async move {
+        let packages_result = git_ref.ask(GetPackages {
+            project_id,
             page
         }).await
-            .or(Err(Error::new(std::io::ErrorKind::Other, "Could not send the action to get the jobs.")))
-            .or(Err(Error::new(std::io::ErrorKind::Other, "Could not find the jobs.")))
-            .unwrap().unwrap();
+            .or(Err(Error::other("Could not send the action to get the packages.")))??;
+        Ok((packages_result.packages, packages_result.next_page))
+    }).await;
+
+    // The latest versions are computed on all the versions of a package, before the date filters.
+    let mut kept_ids: HashSet<u64> = HashSet::new();
+    if args.keep_latest_versions > 0 {
+        let mut versions_by_package: HashMap<(&str, &str), Vec<&Package>> = HashMap::new();
+        for package in &packages {
+            versions_by_package.entry((&package.package_type, &package.name)).or_default().push(package);
+        }
+        for versions in versions_by_package.values_mut() {
+            versions.sort_by_key(|package| Reverse(package.created_at));
+            kept_ids.extend(versions.iter().take(args.keep_latest_versions).map(|package| package.id));
+        }
+    }
+    let packages_to_delete: Vec<Package> = packages.into_iter()
+        .filter(|package| !kept_ids.contains(&package.id))
+        .filter(|package| created_before.is_none_or(|date| package.created_at < date))
+        .filter(|package| created_after.is_none_or(|date| package.created_at > date))
+        .collect();
+
+    process_all(displ_ref, "packages", &packages_to_delete, args, clock, gate, |package| delete_package(git_ref, displ_ref, project_id, package)).await
+        + CleanSummary { truncated, ..CleanSummary::default() }
+}
+
+/// Whether a page already reached items created before the window, in which case the next pages
+/// can be skipped since the listings are sorted newest first (unless told otherwise).
+fn past_window(args: &Args, created_after: Option<DateTime<Utc>>, oldest_created_at: Option<DateTime<Utc>>) -> bool {
+    match (created_after, oldest_created_at) {
+        (Some(after), Some(oldest)) => args.assume_sorted && oldest <= after,
+        _ => false,
+    }
+}
+
+/// Fetch the items of all the pages of a listing, following the next pages within the page range.
+/// Returns the items and whether pages were left out by the page range.
+async fn fetch_pages<T, F, Fut>(
+    displ_ref: &ActorRef<Event, Displ>,
+    name: &str,
+    args: &Args,
+    fetch_page: F) -> (Vec<T>, bool)
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<(Vec<T>, Option<u64>), Error>> {
+    let (items, _, truncated) = fetch_pages_from(displ_ref, name, args, args.page_start.unwrap_or(1), None, fetch_page).await;
+    (items, truncated)
+}
+
+/// Fetch the items of the pages of a listing from the given page, following the next pages within the page range
+/// until no further page fits in the maximum of items, if any, given with the maximum size of a page.
+/// Returns the items, the next page left to fetch and whether pages were left out by the page range.
+async fn fetch_pages_from<T, F, Fut>(
+    displ_ref: &ActorRef<Event, Displ>,
+    name: &str,
+    args: &Args,
+    first_page: u64,
+    max_items: Option<(usize, usize)>,
+    fetch_page: F) -> (Vec<T>, Option<u64>, bool)
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<(Vec<T>, Option<u64>), Error>> {
+    let mut next_page = Some(first_page);
+    let mut items: Vec<T> = Vec::new();
+    let mut truncated = false;
+    // The pages are fetched by windows of parallel pages, merged in the page order. The number of pages being unknown,
+    // the pages of a window after the last page are discarded.
+    let first_excluded_page = args.max_pages.map(|max_pages| args.page_start.unwrap_or(1) + max_pages);
+    'pages: while let Some(page) = next_page {
+        // With a maximum of items, the window only holds the pages fitting in the remaining room.
+        let room = max_items.map_or(usize::MAX, |(max, page_size)| (max.saturating_sub(items.len()) / page_size).max(1));
+        let window: Vec<u64> = (page..page + args.parallel_pages)
+            .take_while(|next| *next == page
+                || (args.page_end.is_none_or(|end| *next <= end) && first_excluded_page.is_none_or(|excluded| *next < excluded)))
+            .take(room)
+            .collect();
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: match window.len() {
+                1 => format!("Loading {} from page {}", name, page),
+                len => format!("Loading {} from pages {} to {}", name, page, page + len as u64 - 1),
+            }
+        }).await;
+
+        for result in futures::future::join_all(window.iter().map(|page| fetch_page(*page))).await {
+            let (mut page_items, page_next) = match result {
+                Ok(page) => page,
+                // In bulk runs, a project lacking the feature listed is skipped rather than failing the whole run.
+                Err(err) if args.ignore_404_on_list && err.kind() == ErrorKind::NotFound => {
+                    let _ = displ_ref.ask(actors::displ::DisplayWarning {
+                        message: format!("skipped the {}: {}", name, err)
+                    }).await;
+                    return (items, None, truncated);
+                }
+                Err(err) => exit_on_error(displ_ref, Error::new(err.kind(), format!("Could not find the {}: {}", name, err))).await,
+            };
 
-        full_jobs.append(jobs_result.jobs.clone().as_mut());
-        jobs_page = jobs_result.next_page;
+            items.append(&mut page_items);
+            next_page = page_next.filter(|next| args.page_end.is_none_or(|end| *next <= end));
+            truncated |= page_next.is_some() && next_page.is_none();
+            // The page numbers are counted from the first page, so that the cap also holds across the chunks of a listing.
+            if let (Some(next), Some(excluded), Some(max_pages)) = (next_page, first_excluded_page, args.max_pages) {
+                if next >= excluded {
+                    let _ = displ_ref.ask(actors::displ::DisplayWarning {
+                        message: format!("stopped loading the {} after {} pages (--max-pages), more pages were announced.", name, max_pages)
+                    }).await;
+                    next_page = None;
+                    truncated = true;
+                }
+            }
+            if next_page.is_none() || max_items.is_some_and(|(max, page_size)| items.len() + page_size > max) {
+                break 'pages;
+            }
+        }
+    };
+    (items, next_page, truncated)
+}
+
+/// Formats a size in bytes with the largest binary unit keeping it above 1, e.g. `1.5 GiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// The number of ids shown as a sample of each group of candidates of a dry run.
+const DRY_RUN_SAMPLE_LEN: usize = 5;
+
+/// The lines listing the candidates of a dry run from the oldest, either one by one or by group with a sample of each.
+fn dry_run_lines<T: Resource>(name: &str, items: &[T], group_by: Option<DryRunGroup>) -> Vec<String> {
+    let mut sorted: Vec<&T> = items.iter().collect();
+    sorted.sort_by_key(|item| (item.created_at(), item.id()));
+    let Some(group_by) = group_by else {
+        return sorted.iter()
+            .map(|item| format!("[dry-run] {} {} created at {} would be erased.", name, item.id(), item.created_at().to_rfc3339()))
+            .collect();
     };
+    let mut groups: BTreeMap<&str, Vec<&T>> = BTreeMap::new();
+    for item in sorted {
+        let key = match group_by {
+            DryRunGroup::Ref => item.git_ref(),
+            DryRunGroup::Status => item.status(),
+        };
+        groups.entry(key.unwrap_or("-")).or_default().push(item);
+    }
+    groups.into_iter()
+        .map(|(key, group)| {
+            let sample: Vec<String> = group.iter().take(DRY_RUN_SAMPLE_LEN).map(|item| item.id().to_string()).collect();
+            format!("[dry-run] {}: {} {} created from {} to {} would be erased, e.g. {}{}",
+                key, group.len(), name,
+                group[0].created_at().to_rfc3339(), group[group.len() - 1].created_at().to_rfc3339(),
+                sample.join(", "), if group.len() > DRY_RUN_SAMPLE_LEN { ", ..." } else { "." })
+        })
+        .collect()
+}
+
+/// The name of a target with the ids and the creation dates of its candidates.
+type Selected = (String, Vec<(u64, DateTime<Utc>)>);
+
+/// The confirmation of the candidates of the targets of a project, previewed and asked once all the targets selected
+/// theirs rather than target by target.
+struct Gate {
+    /// The number of targets yet to select their candidates.
+    pending: std::sync::Mutex<usize>,
+    /// The name of each target that selected its candidates, with their ids and creation dates.
+    selected: std::sync::Mutex<Vec<Selected>>,
+    /// Whether the candidates are confirmed, once decided.
+    decision: tokio::sync::watch::Sender<Option<bool>>,
+    /// Held by the target erasing its candidates, so that the targets listed concurrently are erased one after the other.
+    erasing: tokio::sync::Mutex<()>,
+}
+
+impl Gate {
+    /// Whether the candidates are to be previewed or confirmed before being erased.
+    fn needed(args: &Args) -> bool {
+        !args.dry_run && (args.preview.is_some() || args.confirm_threshold.is_some())
+    }
 
-    let jobs_count: u64 = full_jobs.len() as u64;
+    fn new(targets: usize) -> Self {
+        Gate {
+            pending: std::sync::Mutex::new(targets),
+            selected: std::sync::Mutex::new(Vec::new()),
+            decision: tokio::sync::watch::channel(None).0,
+            erasing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Select the candidates of a target, then wait for the decision on the candidates of all the targets, asked by the
+    /// last target selecting its candidates. Returns whether they are confirmed.
+    async fn select(&self, displ_ref: &ActorRef<Event, Displ>, args: &Args, name: &str, candidates: Vec<(u64, DateTime<Utc>)>) -> bool {
+        self.selected.lock().unwrap().push((name.to_string(), candidates));
+        let last = {
+            let mut pending = self.pending.lock().unwrap();
+            *pending = pending.saturating_sub(1);
+            *pending == 0
+        };
+        if last {
+            let confirmed = self.decide(displ_ref, args).await;
+            self.decision.send_replace(Some(confirmed));
+        }
+        let mut decision = self.decision.subscribe();
+        let confirmed = decision.wait_for(Option::is_some).await.is_ok_and(|decision| *decision == Some(true));
+        confirmed
+    }
+
+    /// Release a target once done, in case it had nothing to select, so that the others are not held back.
+    async fn leave(&self, displ_ref: &ActorRef<Event, Displ>, args: &Args, name: &str) {
+        let selected = self.selected.lock().unwrap().iter().any(|(selected, _)| selected == name);
+        if !selected {
+            self.select(displ_ref, args, name, Vec::new()).await;
+        }
+    }
+
+    /// Preview the candidates of all the targets and ask for a confirmation when asked to.
+    async fn decide(&self, displ_ref: &ActorRef<Event, Displ>, args: &Args) -> bool {
+        let selected: Vec<Selected> = self.selected.lock().unwrap().iter()
+            .filter(|(_, candidates)| !candidates.is_empty())
+            .cloned()
+            .collect();
+        if selected.is_empty() {
+            return true;
+        }
+        let above_threshold = args.confirm_threshold
+            .is_some_and(|threshold| selected.iter().any(|(_, candidates)| candidates.len() as u64 > threshold));
+        let asking = (above_threshold || args.preview.is_some()) && !args.yes;
+        if let Some(preview) = args.preview {
+            for (name, candidates) in &selected {
+                for (id, created_at) in candidates.iter().take(preview) {
+                    let message = format!("[preview] {} {} created at {} will be erased.", name, id, created_at.to_rfc3339());
+                    // The preview goes along with the question, both printed even when the messages are held back.
+                    if asking {
+                        let _ = displ_ref.ask(actors::displ::DisplayPrompt { question: message }).await;
+                    } else {
+                        let _ = displ_ref.ask(actors::displ::DisplayMessage { message }).await;
+                    }
+                }
+            }
+        }
+        if !asking {
+            return true;
+        }
+        let counts: Vec<String> = selected.iter().map(|(name, candidates)| format!("{} {}", candidates.len(), name)).collect();
+        confirm(displ_ref, format!("About to erase {}{}. Proceed? [y/N]",
+            counts.join(", "), if above_threshold { ", above the confirmation threshold" } else { "" })).await
+    }
+}
+
+/// Process all the items with the progress bar, in batches when asked to, then report the errors.
+async fn process_all<'a, T, F, Fut>(
+    displ_ref: &ActorRef<Event, Displ>,
+    name: &str,
+    items: &'a [T],
+    args: &Args,
+    clock: &dyn Clock,
+    gate: Option<&Gate>,
+    process: F) -> CleanSummary
+where
+    T: Resource,
+    F: Fn(&'a T) -> Fut,
+    Fut: Future<Output = Result<EraseOutcome, Error>> {
+    let items_count: u64 = items.len() as u64;
+    if items_count == 0 {
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: "Nothing to clean.".to_string()
+        }).await;
+        return CleanSummary::default();
+    }
+
+    let candidates: Vec<u64> = items.iter().map(|item| item.id()).collect();
+    if args.dry_run {
+        if !args.summary_only {
+            for message in dry_run_lines(name, items, args.dry_run_group_by) {
+                let _ = displ_ref.ask(actors::displ::DisplayMessage { message }).await;
+            }
+        }
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: format!("[dry-run] {} {} would be erased.", items_count, name)
+        }).await;
+        let sizes: Vec<u64> = items.iter().filter_map(|item| item.size()).collect();
+        if !sizes.is_empty() {
+            let _ = displ_ref.ask(actors::displ::DisplayMessage {
+                message: format!("[dry-run] Would reclaim ~{} across {} {}.", format_size(sizes.iter().sum()), sizes.len(), name)
+            }).await;
+        }
+        return CleanSummary {
+            matched: items_count,
+            candidates,
+            ..CleanSummary::default()
+        };
+    }
+
+    // The targets confirmed together are erased one after the other, unless asked otherwise.
+    let mut _erasing = None;
+    if let Some(gate) = gate {
+        let selected = items.iter().map(|item| (item.id(), item.created_at())).collect();
+        if !gate.select(displ_ref, args, name, selected).await {
+            let _ = displ_ref.ask(actors::displ::DisplayMessage {
+                message: format!("Skipping the cleanup of the {}.", name)
+            }).await;
+            return CleanSummary {
+                matched: items_count,
+                candidates,
+                ..CleanSummary::default()
+            };
+        }
+        if !args.parallel_targets {
+            _erasing = Some(gate.erasing.lock().await);
+        }
+    }
 
     let _ = displ_ref.ask(actors::displ::DisplayMessage {
-        message: format!("Found {} jobs to clean.", jobs_count)
+        message: format!("Found {} {} to clean.", items_count, name)
     }).await;
     displ_ref.ask(actors::displ::InitProgressBar {
-        length: jobs_count,
-        message: format!("Cleaning the jobs...")
+        bar: name.to_string(),
+        length: items_count,
+        message: format!("Cleaning the {}...", name)
     }).await
-        .or(Err(Error::new(std::io::ErrorKind::Other, "Could not prepare the progress bar somehow."))).unwrap();
+        .or(Err(Error::other("Could not prepare the progress bar somehow."))).unwrap();
 
-    let future_results = full_jobs.iter().map(|job| async {
-        git_ref.ask(actors::git::EraseJob {
-            project_id,
-            job_id: job.id
-        }).await
-            .or(Err(Error::new(std::io::ErrorKind::Other, format!("Could not send the action to erase the job {}", job.id))))?
-            .or(Err(Error::new(std::io::ErrorKind::Other, format!("Could not erase the job {}", job.id))))?;
+    // In ordered mode, the items are processed one at a time by id so that the logs are reproducible.
+    let mut items: Vec<&'a T> = items.iter().collect();
+    if args.ordered {
+        items.sort_by_key(|item| item.id());
+    }
+    // Without a batch size, all the items are processed in a single batch.
+    let batch_size = args.batch_size.unwrap_or(items.len()).max(1);
+    let mut results: Vec<Result<EraseOutcome, Error>> = Vec::new();
+    for (index, batch) in items.chunks(batch_size).enumerate() {
+        if index > 0 && args.batch_pause_secs > 0 {
+            clock.sleep(std::time::Duration::from_secs(args.batch_pause_secs)).await;
+        }
+        // The results are kept in the order of the items, even when the concurrency is bounded.
+        let future_results = batch.iter().map(|item| process(item));
+        results.append(&mut futures::stream::iter(future_results).buffered(args.concurrency()).collect().await);
+    }
 
-        let _ = displ_ref.ask(actors::displ::IncreaseProgress {
-            message: format!("Job {} erased.", job.id)
+    // The failed items are re-attempted in final passes, waiting twice as long before each pass within the backoff bounds.
+    let backoff = args.backoff();
+    let mut waited = std::time::Duration::ZERO;
+    for pass in 1..=args.retry_failed {
+        let failed_indexes: Vec<usize> = results.iter().enumerate()
+            .filter(|(_, result)| result.is_err())
+            .map(|(index, _)| index)
+            .collect();
+        if failed_indexes.is_empty() {
+            break;
+        }
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: format!("Retrying {} failed {} (pass {} of {}).", failed_indexes.len(), name, pass, args.retry_failed)
         }).await;
-        Ok(())
-    });
+        let Some(delay) = backoff.next(std::time::Duration::from_secs(1 << (pass - 1).min(6)), waited) else {
+            let _ = displ_ref.ask(actors::displ::DisplayMessage {
+                message: backoff.timeout_error(waited, &format!("{} {} still failing", failed_indexes.len(), name)).to_string()
+            }).await;
+            break;
+        };
+        clock.sleep(delay).await;
+        waited += delay;
+        // The retries are bounded by the concurrency like the first pass.
+        let future_results = failed_indexes.iter().map(|index| process(items[*index]));
+        let retried_results: Vec<_> = futures::stream::iter(future_results).buffered(args.concurrency()).collect().await;
+        for (index, result) in failed_indexes.into_iter().zip(retried_results) {
+            results[index] = result;
+        }
+    }
+    let _ = displ_ref.ask(actors::displ::FinishProgress { bar: name.to_string() }).await;
 
-    let results: Vec<Result<(), Error>> = futures::future::join_all(future_results).await;
-    results.iter().filter(|r| r.is_err()).for_each(|r| {
-        println!("Error: {}", r.as_ref().unwrap_err());
-    });
+    let mut summary = CleanSummary {
+        matched: items_count,
+        candidates,
+        ..CleanSummary::default()
+    };
+    for (item, result) in items.iter().zip(results.iter()) {
+        match result {
+            Ok(EraseOutcome::Erased | EraseOutcome::Accepted) => {
+                summary.erased += 1;
+                summary.erased_ids.push(item.id());
+            }
+            Ok(EraseOutcome::AlreadyGone) => summary.skipped += 1,
+            Err(err) => {
+                summary.failed += 1;
+                let _ = displ_ref.ask(actors::displ::DisplayMessage {
+                    message: format!("Error: {}", err)
+                }).await;
+            }
+        }
+    }
 
     let _ = displ_ref.ask(actors::displ::DisplayMessage {
-        message: "Done erasing jobs." .to_string()
+        message: format!("Done erasing {}: {} erased, {} skipped, {} failed.", name, summary.erased, summary.skipped, summary.failed)
     }).await;
+    summary
 }
 
+/// Ask a yes/no question on the terminal, the answer being no when stdin is not a terminal.
+async fn confirm(displ_ref: &ActorRef<Event, Displ>, question: String) -> bool {
+    if !std::io::stdin().is_terminal() {
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: "Confirmation required but stdin is not a terminal: use --yes to proceed.".to_string()
+        }).await;
+        return false;
+    }
+    ask(displ_ref, question).await
+        .is_some_and(|answer| matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Ask a question on the terminal and read the answer line, none when stdin is not a terminal.
+async fn ask(displ_ref: &ActorRef<Event, Displ>, question: String) -> Option<String> {
+    if !std::io::stdin().is_terminal() {
+        let _ = displ_ref.ask(actors::displ::DisplayMessage {
+            message: format!("{} An answer is required but stdin is not a terminal.", question)
+        }).await;
+        return None;
+    }
+    let _ = displ_ref.ask(actors::displ::DisplayPrompt { question }).await;
+    let mut answer = String::new();
+    let mut stdin = tokio::io::BufReader::new(tokio::io::stdin());
+    tokio::io::AsyncBufReadExt::read_line(&mut stdin, &mut answer).await.ok()?;
+    Some(answer)
+}
+
+/// Probe whether the token can read, or erase, a job in a dry run, reporting the outcome.
+async fn probe_job(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>, project_id: u64, job: &Job, mode: &ProbeMode) {
+    let result = match mode {
+        ProbeMode::Head => git_ref.ask(ProbeJob { project_id, job_id: job.id }).await
+            .map_err(|err| Error::other(err.to_string()))
+            .and_then(|res| res),
+        ProbeMode::Erase => git_ref.ask(actors::git::EraseJob { project_id, job_id: job.id }).await
+            .map_err(|err| Error::other(err.to_string()))
+            .and_then(|res| res.map(|_| ())),
+    };
+    let message = match result {
+        Ok(()) if *mode == ProbeMode::Erase => format!("[dry-run] Probe: the job {} was erased, the token is authorized to erase jobs.", job.id),
+        Ok(()) => format!("[dry-run] Probe: the job {} can be read with the token, which does not prove it can be erased (--dry-run-probe erase does).", job.id),
+        Err(err) if *mode == ProbeMode::Erase => format!("[dry-run] Probe: the token is not authorized to erase the job {}: {}", job.id, err),
+        Err(err) => format!("[dry-run] Probe: the token cannot read the job {}: {}", job.id, err),
+    };
+    let _ = displ_ref.ask(actors::displ::DisplayMessage { message }).await;
+}
+
+/// Wait for the reset of the rate limit when it is nearly reached, displaying a countdown.
+async fn pause_on_rate_limit(git_ref: &GitPool, displ_ref: &ActorRef<Event, Displ>) {
+    if let Ok(Some(until)) = git_ref.ask(GetPause).await {
+        let _ = displ_ref.ask(actors::displ::Countdown {
+            message: "The rate limit is nearly reached, pausing until its reset".to_string(),
+            until
+        }).await;
+    }
+}
+
+/// Erase a single job and advance the progress bar.
+async fn erase_job(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    job: &Job,
+    args: &Args,
+    clock: &dyn Clock) -> Result<EraseOutcome, Error> {
+    pause_on_rate_limit(git_ref, displ_ref).await;
+    let mut outcome = git_ref.ask(actors::git::EraseJob {
+        project_id,
+        job_id: job.id
+    }).await
+        .or(Err(Error::other(format!("Could not send the action to erase the job {}", job.id))))?
+        .map_err(|err| Error::new(err.kind(), format!("Could not erase the job {}: {}", job.id, err)))?;
+    // Without verification, an erase accepted to be processed asynchronously is considered done.
+    if outcome == EraseOutcome::Accepted && args.verify {
+        verify_erased(git_ref, clock, project_id, job.id, std::time::Duration::from_secs(args.verify_timeout_secs)).await?;
+        outcome = EraseOutcome::Erased;
+    }
+
+    let _ = displ_ref.ask(match outcome {
+        EraseOutcome::Erased | EraseOutcome::Accepted => actors::displ::IncreaseProgress {
+            bar: "jobs",
+            message: format!("Job {} erased.", job.id),
+            id: job.id,
+            event: "erased"
+        },
+        EraseOutcome::AlreadyGone => actors::displ::IncreaseProgress {
+            bar: "jobs",
+            message: format!("Job {} already gone, skipped.", job.id),
+            id: job.id,
+            event: "skipped"
+        },
+    }).await;
+    Ok(outcome)
+}
+
+/// Poll a job until its erase date is set, or until the timeout is reached, waiting on the clock between the polls.
+async fn verify_erased(git_ref: &GitPool, clock: &dyn Clock, project_id: u64, job_id: u64, timeout: std::time::Duration) -> Result<(), Error> {
+    let started_at = clock.instant();
+    loop {
+        match git_ref.ask(GetJob { project_id, job_id }).await.map_err(|err| Error::other(err.to_string()))? {
+            Ok(job) if job.erased_at.is_some() => return Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            _ if clock.instant() - started_at >= timeout => return Err(Error::new(ErrorKind::TimedOut, format!(
+                "The erase of the job {} was accepted but not done after {:?}.", job_id, timeout))),
+            _ => clock.sleep(std::time::Duration::from_secs(1)).await,
+        }
+    }
+}
+
+/// Delete a single pipeline and advance the progress bar.
+async fn delete_pipeline(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    pipeline: &Pipeline) -> Result<EraseOutcome, Error> {
+    pause_on_rate_limit(git_ref, displ_ref).await;
+    let outcome = git_ref.ask(actors::git::DeletePipeline {
+        project_id,
+        pipeline_id: pipeline.id
+    }).await
+        .or(Err(Error::other(format!("Could not send the action to delete the pipeline {}", pipeline.id))))?
+        .map_err(|err| Error::new(err.kind(), format!("Could not delete the pipeline {}: {}", pipeline.id, err)))?;
+
+    let _ = displ_ref.ask(actors::displ::IncreaseProgress {
+        bar: "pipelines",
+        message: format!("Pipeline {} deleted.", pipeline.id),
+        id: pipeline.id,
+        event: "deleted"
+    }).await;
+    Ok(outcome)
+}
+
+/// Delete the artifacts of a single job and advance the progress bar.
+async fn delete_job_artifacts(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    job: &Job) -> Result<EraseOutcome, Error> {
+    pause_on_rate_limit(git_ref, displ_ref).await;
+    let outcome = git_ref.ask(actors::git::DeleteJobArtifacts {
+        project_id,
+        job_id: job.id
+    }).await
+        .or(Err(Error::other(format!("Could not send the action to delete the artifacts of the job {}", job.id))))?
+        .map_err(|err| Error::new(err.kind(), format!("Could not delete the artifacts of the job {}: {}", job.id, err)))?;
+
+    let _ = displ_ref.ask(actors::displ::IncreaseProgress {
+        bar: "artifacts",
+        message: format!("Artifacts of the job {} deleted.", job.id),
+        id: job.id,
+        event: "deleted"
+    }).await;
+    Ok(outcome)
+}
+
+/// Delete a single deployment and advance the progress bar.
+async fn delete_deployment(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    deployment: &Deployment) -> Result<EraseOutcome, Error> {
+    pause_on_rate_limit(git_ref, displ_ref).await;
+    let outcome = git_ref.ask(actors::git::DeleteDeployment {
+        project_id,
+        deployment_id: deployment.id
+    }).await
+        .or(Err(Error::other(format!("Could not send the action to delete the deployment {}", deployment.id))))?
+        .map_err(|err| Error::new(err.kind(), format!("Could not delete the deployment {}: {}", deployment.id, err)))?;
+
+    let _ = displ_ref.ask(actors::displ::IncreaseProgress {
+        bar: "deployments",
+        message: format!("Deployment {} of {} deleted.", deployment.id, deployment.environment.name),
+        id: deployment.id,
+        event: "deleted"
+    }).await;
+    Ok(outcome)
+}
+
+/// Delete a single package version and advance the progress bar.
+async fn delete_package(
+    git_ref: &GitPool,
+    displ_ref: &ActorRef<Event, Displ>,
+    project_id: u64,
+    package: &Package) -> Result<EraseOutcome, Error> {
+    pause_on_rate_limit(git_ref, displ_ref).await;
+    let outcome = git_ref.ask(actors::git::DeletePackage {
+        project_id,
+        package_id: package.id
+    }).await
+        .or(Err(Error::other(format!("Could not send the action to delete the package {}", package.id))))?
+        .map_err(|err| Error::new(err.kind(), format!("Could not delete the package {} {}: {}", package.name, package.version, err)))?;
+
+    let _ = displ_ref.ask(actors::displ::IncreaseProgress {
+        bar: "packages",
+        message: format!("Package {} {} deleted.", package.name, package.version),
+        id: package.id,
+        event: "deleted"
+    }).await;
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::{atomic::{AtomicUsize, Ordering}, Arc}, time::Duration};
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::{clock::{MockClock, SystemClock}, mock::{MockResponse, MockServer}};
+
+    fn args(extra: &[&str]) -> Args {
+        Args::try_parse_from(["gitlab-cleaner", "--gitlab-url", "https://host", "-p", "project"].iter().chain(extra)).unwrap()
+    }
+
+    /// A job as listed by the Gitlab API.
+    fn job_json(id: u64, created_at: &str) -> serde_json::Value {
+        serde_json::json!({ "id": id, "created_at": created_at, "status": "failed", "ref": "main" })
+    }
+
+    fn job(id: u64, created_at: &str, status: &str, git_ref: &str) -> Job {
+        serde_json::from_value(serde_json::json!({ "id": id, "created_at": created_at, "status": status, "ref": git_ref })).unwrap()
+    }
+
+    /// A mock instance listing the jobs on a single page, created on the 1st of January 2024, and erasing them.
+    async fn erasing_server(ids: impl IntoIterator<Item = u64>) -> MockServer {
+        let jobs: Vec<_> = ids.into_iter().map(|id| job_json(id, "2024-01-01T00:00:00Z")).collect();
+        MockServer::start(move |request| match (request.method.as_str(), request.query("page")) {
+            ("POST", _) => MockResponse::json(201, job_json(1, "2024-01-01T00:00:00Z")),
+            (_, Some("1")) => MockResponse::json(200, serde_json::json!(jobs)),
+            _ => MockResponse::json(200, serde_json::json!([])),
+        }).await
+    }
+
+    /// The actors of a run against the mock instance, as many Git actors as the run needs, the messages being held back.
+    async fn actors(server: &MockServer, args: &Args, config: GitConfig) -> (GitPool, ActorRef<Event, Displ>) {
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        let clock = config.clock.clone().unwrap_or(Arc::new(SystemClock));
+        let system = ActorSystem::new("test", EventBus::new(1000));
+        let git = Git::new(GitConfig {
+            base_url: server.url.clone(),
+            token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+            ..config
+        }).unwrap();
+        let git_ref = GitPool::create(&system, "git-actor", git, args.git_actors()).await.unwrap();
+        let displ = Displ::new(DisplConfig { colors: false, buffered: true, clock, ..DisplConfig::default() });
+        (git_ref, system.create_actor("displ-actor", displ).await.unwrap())
+    }
+
+    /// The messages displayed so far, held back by the display.
+    async fn displayed(displ_ref: &ActorRef<Event, Displ>) -> Vec<String> {
+        displ_ref.ask(actors::displ::FlushMessages { print: false }).await.unwrap()
+    }
+
+    /// A file of the temporary directory unique to the test, removed beforehand.
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("gitlab-cleaner-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn parallel_pages_are_fetched_concurrently_and_merged_in_order() {
+        let server = MockServer::start(|request| {
+            let page: u64 = request.query("page").unwrap().parse().unwrap();
+            if page > 6 {
+                return MockResponse::json(200, serde_json::json!([]));
+            }
+            // The first pages answer last, so that the merge cannot follow the order of the answers.
+            MockResponse::json(200, serde_json::json!([job_json(100 - page, "2024-01-01T00:00:00Z")]))
+                .header("X-Next-Page", if page < 6 { (page + 1).to_string() } else { String::new() })
+                .delayed(Duration::from_millis(250 - 40 * page))
+        }).await;
+        let args = args(&["--dry-run", "--parallel-pages", "4"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        assert_eq!(summary.candidates, vec![99, 98, 97, 96, 95, 94]);
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn parallel_targets_are_listed_concurrently() {
+        let server = MockServer::start(|request| {
+            let listed = if request.path.contains("/packages") {
+                serde_json::json!([{ "id": 7, "name": "lib", "version": "1.0.0", "package_type": "npm", "created_at": "2024-01-01T00:00:00Z" }])
+            } else {
+                serde_json::json!([job_json(3, "2024-01-01T00:00:00Z")])
+            };
+            MockResponse::json(200, listed).delayed(Duration::from_millis(200))
+        }).await;
+        let args = args(&["--dry-run", "-t", "jobs,packages", "--parallel-targets"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summaries = clean_project(&git_ref, &displ_ref, 1, &args.target, None, None, &args, &SystemClock).await;
+        assert_eq!(summaries.iter().map(|summary| summary.candidates.clone()).collect::<Vec<_>>(), vec![vec![3], vec![7]]);
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn erases_are_bounded_by_the_concurrency() {
+        let server = MockServer::start(|request| {
+            if request.method == "POST" {
+                return MockResponse::json(201, job_json(1, "2024-01-01T00:00:00Z")).delayed(Duration::from_millis(100));
+            }
+            let page: u64 = request.query("page").unwrap().parse().unwrap();
+            let jobs: Vec<_> = if page == 1 { (1..=12).map(|id| job_json(id, "2024-01-01T00:00:00Z")).collect() } else { Vec::new() };
+            MockResponse::json(200, serde_json::json!(jobs))
+        }).await;
+        let args = args(&["--concurrency", "3"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        assert_eq!(summary.erased, 12);
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn the_concurrency_defaults_to_a_bounded_number_of_actors() {
+        assert_eq!(args(&[]).concurrency(), cli::DEFAULT_CONCURRENCY);
+        assert_eq!(args(&[]).git_actors(), cli::DEFAULT_CONCURRENCY);
+        assert_eq!(args(&["--ordered"]).git_actors(), 1);
+        assert_eq!(args(&["--parallel-pages", "12", "--concurrency", "2"]).git_actors(), 12);
+    }
+
+    #[test]
+    fn printed_ids_keep_stdout_to_themselves() {
+        let parsed = Args::try_parse_from(["gitlab-cleaner", "--gitlab-url", "https://host", "-p", "project", "--print-ids", "--inspect-job", "1"]);
+        assert_eq!(parsed.err().unwrap().kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[tokio::test]
+    async fn the_state_file_is_not_advanced_by_a_truncated_run() {
+        let server = MockServer::start(|request| {
+            if request.path.starts_with("/api/v4/projects/1?") || request.path == "/api/v4/projects/1" {
+                return MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project" }));
+            }
+            if request.method == "POST" {
+                return MockResponse::json(201, job_json(1, "2024-01-01T00:00:00Z"));
+            }
+            let page: u64 = request.query("page").unwrap().parse().unwrap();
+            let jobs: Vec<_> = if page == 1 { (1..=2).map(|id| job_json(id, "2024-01-01T00:00:00Z")).collect() } else { Vec::new() };
+            MockResponse::json(200, serde_json::json!(jobs))
+        }).await;
+        let state_file = std::env::temp_dir().join(format!("gitlab-cleaner-state-{}", std::process::id()));
+        for (extra, written) in [(&["--limit", "1"][..], false), (&[][..], true)] {
+            let _ = std::fs::remove_file(&state_file);
+            let args = Args::try_parse_from(["gitlab-cleaner", "--gitlab-url", "https://host", "--project-id", "1", "--skip-version-check",
+                "--state-file", state_file.to_str().unwrap()].iter().chain(extra)).unwrap();
+            let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+            assert_eq!(run(&git_ref, &displ_ref, &args, &SystemClock).await, 0);
+            assert_eq!(state_file.exists(), written, "{:?}", extra);
+        }
+        let _ = std::fs::remove_file(&state_file);
+    }
+
+    #[tokio::test]
+    async fn the_listings_cut_by_the_page_range_are_truncated() {
+        let server = MockServer::start(|request| {
+            let page: u64 = request.query("page").unwrap().parse().unwrap();
+            MockResponse::json(200, serde_json::json!([job_json(10 - page, "2024-01-01T00:00:00Z")])).header("X-Next-Page", page + 1)
+        }).await;
+        for extra in [["--page-end", "2"], ["--max-pages", "2"]] {
+            let args = args(&["--dry-run", "-t", "artifacts"].iter().chain(&extra).copied().collect::<Vec<_>>());
+            let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+            let summary = clean_project(&git_ref, &displ_ref, 1, &args.target, None, None, &args, &SystemClock).await;
+            assert!(summary[0].truncated, "{:?}", extra);
+        }
+    }
+
+    /// A sink of the events shared with the test.
+    #[derive(Clone, Default)]
+    struct Sink(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_the_subscribers_to_write_the_last_events() {
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        let system = ActorSystem::new("test", EventBus::new(1000));
+        let sink = Sink::default();
+        let subscribers = vec![tokio::spawn(actors::event::write_ndjson(system.events(), sink.clone()))];
+        let git = Git::new(GitConfig {
+            base_url: "https://host".to_string(),
+            token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+            ..GitConfig::default()
+        }).unwrap();
+        let git_ref = GitPool::create(&system, "git-actor", git, 2).await.unwrap();
+        let displ_ref = system.create_actor("displ-actor", Displ::new(DisplConfig { buffered: true, ..DisplConfig::default() })).await.unwrap();
+        for id in 0..100 {
+            system.publish(Event::Erased { target: "jobs", project_id: 1, id });
+        }
+        tokio::time::timeout(Duration::from_secs(5), shutdown(system, git_ref, displ_ref, subscribers)).await.unwrap();
+        assert_eq!(String::from_utf8(sink.0.lock().unwrap().clone()).unwrap().lines().count(), 100);
+    }
+
+    #[tokio::test]
+    async fn retries_are_bounded_by_the_concurrency() {
+        let attempted = std::sync::Mutex::new(HashSet::new());
+        let server = MockServer::start(move |request| {
+            if request.method == "POST" {
+                // Each erase fails once, then succeeds when retried.
+                let status = if attempted.lock().unwrap().insert(request.path.clone()) { 400 } else { 201 };
+                return MockResponse::json(status, job_json(1, "2024-01-01T00:00:00Z")).delayed(Duration::from_millis(50));
+            }
+            let page: u64 = request.query("page").unwrap().parse().unwrap();
+            let jobs: Vec<_> = if page == 1 { (1..=6).map(|id| job_json(id, "2024-01-01T00:00:00Z")).collect() } else { Vec::new() };
+            MockResponse::json(200, serde_json::json!(jobs))
+        }).await;
+        // The pool of actors is larger than the concurrency, so that only the concurrency bounds the retries, the jobs
+        // being listed from a single page.
+        let args = args(&["--concurrency", "2", "--retry-failed", "1", "--parallel-pages", "8", "--page-end", "1"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let clock = MockClock::new(Utc::now());
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &clock, None).await;
+        assert_eq!((summary.erased, summary.failed), (6, 0));
+        assert_eq!(server.paths("POST").len(), 12);
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 2);
+        assert_eq!(*clock.sleeps.lock().unwrap(), vec![Duration::from_secs(1)]);
+    }
+
+    #[test]
+    fn keep_success_takes_precedence_over_the_status() {
+        let job = |status: &str| serde_json::from_value::<Job>(serde_json::json!({ "id": 1, "created_at": "2024-01-01T00:00:00Z", "status": status, "ref": "main" })).unwrap();
+        let args = args(&["--status", "success", "--status", "failed", "--keep-success"]);
+        assert!(!job_matches(&job("success"), &args, Utc::now()));
+        assert!(job_matches(&job("failed"), &args, Utc::now()));
+    }
+
+    #[tokio::test]
+    async fn accepted_erases_are_polled_on_the_clock_until_erased() {
+        let polls = AtomicUsize::new(0);
+        let server = MockServer::start(move |request| {
+            if request.method == "POST" {
+                return MockResponse::empty(202);
+            }
+            // The erase is done on the second poll.
+            let erased_at = (polls.fetch_add(1, Ordering::SeqCst) > 0).then_some("2024-01-02T00:00:00Z");
+            MockResponse::json(200, serde_json::json!({ "id": 5, "created_at": "2024-01-01T00:00:00Z", "status": "failed", "ref": "main", "erased_at": erased_at }))
+        }).await;
+        let args = args(&["--verify"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let clock = MockClock::new(Utc::now());
+        let job = serde_json::from_value::<Job>(job_json(5, "2024-01-01T00:00:00Z")).unwrap();
+        let outcome = erase_job(&git_ref, &displ_ref, 1, &job, &args, &clock).await.unwrap();
+        assert_eq!(outcome, EraseOutcome::Erased);
+        assert_eq!(server.paths("GET"), vec!["/api/v4/projects/1/jobs/5", "/api/v4/projects/1/jobs/5"]);
+        assert_eq!(*clock.sleeps.lock().unwrap(), vec![Duration::from_secs(1)]);
+    }
+
+    #[tokio::test]
+    async fn accepted_erases_time_out_on_the_clock() {
+        let server = MockServer::start(|request| match request.method.as_str() {
+            "POST" => MockResponse::empty(202),
+            _ => MockResponse::json(200, job_json(5, "2024-01-01T00:00:00Z")),
+        }).await;
+        let args = args(&["--verify", "--verify-timeout-secs", "3"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let clock = MockClock::new(Utc::now());
+        let job = serde_json::from_value::<Job>(job_json(5, "2024-01-01T00:00:00Z")).unwrap();
+        let err = erase_job(&git_ref, &displ_ref, 1, &job, &args, &clock).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert_eq!(clock.sleeps.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn the_loop_runs_cycles_separated_by_the_interval_on_the_clock() {
+        let stop = Arc::new(tokio::sync::Notify::new());
+        let (stopping, listings) = (stop.clone(), AtomicUsize::new(0));
+        let server = MockServer::start(move |request| {
+            if !request.path.contains("/jobs") {
+                return MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project" }));
+            }
+            // Stop is asked during the second cycle, which is then completed.
+            if listings.fetch_add(1, Ordering::SeqCst) == 1 {
+                stopping.notify_one();
+            }
+            MockResponse::json(200, serde_json::json!([job_json(1, "2024-01-01T00:00:00Z")]))
+        }).await;
+        let args = Args::try_parse_from(["gitlab-cleaner", "--gitlab-url", "https://host", "--project-id", "1", "--skip-version-check",
+            "--dry-run", "--loop", "15m"]).unwrap();
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let clock = MockClock::new(Utc::now());
+        let exit_code = run_loop(&git_ref, &displ_ref, &args, &clock, args.interval.unwrap(), stop).await;
+        assert_eq!(exit_code, 0);
+        assert_eq!(server.paths("GET").iter().filter(|path| path.contains("/jobs")).count(), 2);
+        assert_eq!(*clock.sleeps.lock().unwrap(), vec![Duration::from_secs(15 * 60)]);
+    }
+
+    #[tokio::test]
+    async fn only_the_pages_of_the_range_are_fetched() {
+        let server = MockServer::start(|request| {
+            let page: u64 = request.query("page").unwrap().parse().unwrap();
+            MockResponse::json(200, serde_json::json!([job_json(10 - page, "2024-01-01T00:00:00Z")])).header("X-Next-Page", page + 1)
+        }).await;
+        let args = args(&["--dry-run", "--page-start", "2", "--page-end", "3"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        assert_eq!(summary.candidates, vec![8, 7]);
+        let pages: Vec<String> = server.requests.lock().unwrap().iter().map(|request| request.query("page").unwrap().to_string()).collect();
+        assert_eq!(pages, vec!["2", "3"]);
+    }
+
+    #[tokio::test]
+    async fn batches_are_separated_by_a_pause_on_the_clock() {
+        let server = erasing_server(1..=5).await;
+        let args = args(&["--batch-size", "2", "--batch-pause-secs", "3"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let clock = MockClock::new(Utc::now());
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &clock, None).await;
+        assert_eq!(summary.erased, 5);
+        assert_eq!(*clock.sleeps.lock().unwrap(), vec![Duration::from_secs(3), Duration::from_secs(3)]);
+    }
+
+    #[tokio::test]
+    async fn an_old_gitlab_version_is_reported_as_a_warning() {
+        let server = MockServer::start(|_| MockResponse::json(200, serde_json::json!({ "version": "15.2.0-ee", "revision": "abc" }))).await;
+        let args = args(&[]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        check_version(&git_ref, &displ_ref, &Target::Jobs).await;
+        check_version(&git_ref, &displ_ref, &Target::Deployments).await;
+        assert_eq!(displayed(&displ_ref).await, vec![
+            "Warning: the Gitlab instance runs version 15.2.0-ee but cleaning deployments requires at least 15.3. Some endpoints might be missing.",
+        ]);
+        assert_eq!(server.paths("GET"), vec!["/api/v4/version", "/api/v4/version"]);
+    }
+
+    #[tokio::test]
+    async fn an_empty_listing_has_nothing_to_clean() {
+        let server = erasing_server([]).await;
+        let args = args(&[]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        assert_eq!((summary.matched, summary.erased), (0, 0));
+        assert_eq!(displayed(&displ_ref).await, vec!["Loading jobs from page 1", "Nothing to clean."]);
+        assert!(server.paths("POST").is_empty());
+    }
+
+    #[tokio::test]
+    async fn summary_only_keeps_the_dry_run_totals() {
+        let server = erasing_server(1..=2).await;
+        let args = args(&["--dry-run", "--summary-only"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        assert_eq!(displayed(&displ_ref).await, vec![
+            "Loading jobs from page 1",
+            "[dry-run] 2 jobs would be erased.",
+            "[dry-run] Would reclaim ~0 B across 2 jobs.",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn nothing_matched_fails_only_with_fail_on_empty() {
+        let server = erasing_server([]).await;
+        for (extra, code) in [(&[][..], 0), (&["--fail-on-empty"][..], 2)] {
+            let args = args(extra);
+            let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+            let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+            assert_eq!(summary.exit_code(args.fail_on_empty), code);
+        }
+        let summary = CleanSummary { matched: 1, ..CleanSummary::default() };
+        assert_eq!(summary.exit_code(true), 0);
+        assert_eq!(CleanSummary { failed: 1, ..summary }.exit_code(true), 1);
+    }
+
+    #[test]
+    fn state_file_records_the_cutoff() {
+        let path = temp_file("state");
+        let cutoff = parse_date("2024-01-01T00:00:00Z").unwrap();
+        assert_eq!(read_last_run(&path).unwrap(), None);
+        write_last_run(&path, cutoff).unwrap();
+        assert_eq!(read_last_run(&path).unwrap(), Some(cutoff));
+        std::fs::write(&path, "yesterday").unwrap();
+        assert_eq!(read_last_run(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_latest_versions_of_each_package_are_kept() {
+        let server = MockServer::start(|request| match (request.method.as_str(), request.query("page")) {
+            ("DELETE", _) => MockResponse::empty(204),
+            (_, Some("1")) => MockResponse::json(200, serde_json::json!([
+                { "id": 4, "name": "app", "version": "1.2.0", "package_type": "npm", "created_at": "2024-03-01T00:00:00Z" },
+                { "id": 3, "name": "app", "version": "1.1.0", "package_type": "npm", "created_at": "2024-02-01T00:00:00Z" },
+                { "id": 2, "name": "app", "version": "1.0.0", "package_type": "npm", "created_at": "2024-01-01T00:00:00Z" },
+                { "id": 1, "name": "lib", "version": "0.1.0", "package_type": "maven", "created_at": "2024-01-01T00:00:00Z" },
+            ])),
+            _ => MockResponse::json(200, serde_json::json!([])),
+        }).await;
+        let args = args(&["-t", "packages", "--keep-latest-versions", "2"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summary = clean_packages(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        assert_eq!((summary.matched, summary.erased), (1, 1));
+        assert_eq!(server.paths("DELETE"), vec!["/api/v4/projects/1/packages/2"]);
+    }
+
+    #[test]
+    fn diff_snapshots_lists_the_added_and_removed_ids() {
+        let previous = Snapshot::from([("jobs".to_string(), vec![1, 2, 3]), ("pipelines".to_string(), vec![9])]);
+        let current = Snapshot::from([("jobs".to_string(), vec![2, 3, 4]), ("7/jobs".to_string(), vec![5])]);
+        let diff = diff_snapshots(&previous, &current);
+        assert_eq!(diff["jobs"], (vec![4], vec![1]));
+        assert_eq!(diff["pipelines"], (vec![], vec![9]));
+        assert_eq!(diff["7/jobs"], (vec![5], vec![]));
+    }
+
+    #[test]
+    fn snapshot_key_is_prefixed_with_the_project_id() {
+        assert_eq!(snapshot_key(false, 7, &Target::Jobs), "jobs");
+        assert_eq!(snapshot_key(true, 7, &Target::Jobs), "7/jobs");
+    }
+
+    #[tokio::test]
+    async fn the_buffered_jobs_never_exceed_the_cap() {
+        // The jobs listed but not erased yet, the erases being slow.
+        let buffered = Arc::new(AtomicUsize::new(0));
+        let max_buffered = Arc::new(AtomicUsize::new(0));
+        let (counter, max) = (buffered.clone(), max_buffered.clone());
+        let server = MockServer::start(move |request| {
+            if request.method == "POST" {
+                counter.fetch_sub(1, Ordering::SeqCst);
+                return MockResponse::json(201, job_json(1, "2024-01-01T00:00:00Z")).delayed(Duration::from_millis(20));
+            }
+            assert_eq!(request.query("per_page"), Some("3"));
+            let page: u64 = request.query("page").unwrap().parse().unwrap();
+            let jobs: Vec<_> = (1..=3).map(|id| job_json((page - 1) * 3 + id, "2024-01-01T00:00:00Z")).collect();
+            let listed = counter.fetch_add(jobs.len(), Ordering::SeqCst) + jobs.len();
+            max.fetch_max(listed, Ordering::SeqCst);
+            let response = MockResponse::json(200, serde_json::json!(jobs));
+            if page < 4 { response.header("X-Next-Page", page + 1) } else { response }
+        }).await;
+        let args = args(&["--max-buffered-jobs", "3"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        assert_eq!(summary.erased, 12);
+        assert_eq!(max_buffered.load(Ordering::SeqCst), 3);
+        assert!(displayed(&displ_ref).await.iter().any(|line| line.contains("the buffer of 3 jobs is full (--max-buffered-jobs)")));
+    }
+
+    #[tokio::test]
+    async fn a_confirmation_is_only_asked_above_the_threshold() {
+        let server = erasing_server(1..=2).await;
+        let mut cases = vec![(&["--confirm-threshold", "2"][..], 2), (&["--confirm-threshold", "1", "--yes"][..], 2)];
+        // The confirmation is refused when no answer can be read, but awaited on a terminal.
+        if !std::io::stdin().is_terminal() {
+            cases.push((&["--confirm-threshold", "1"][..], 0));
+        }
+        for (extra, erased) in cases {
+            let args = args(extra);
+            let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+            let gate = Gate::new(1);
+            let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, Some(&gate)).await;
+            assert_eq!(summary.erased, erased, "{:?}", extra);
+            let refused = displayed(&displ_ref).await.iter().any(|line| line.starts_with("Confirmation required"));
+            assert_eq!(refused, erased == 0, "{:?}", extra);
+        }
+    }
+
+    #[tokio::test]
+    async fn each_status_is_capped_independently() {
+        let server = MockServer::start(|request| match (request.method.as_str(), request.query("page")) {
+            ("POST", _) => MockResponse::json(201, job_json(1, "2024-01-01T00:00:00Z")),
+            (_, Some("1")) => MockResponse::json(200, serde_json::json!((1..=6).map(|id| serde_json::json!({
+                "id": id,
+                "created_at": "2024-01-01T00:00:00Z",
+                "status": if id % 2 == 0 { "failed" } else { "canceled" },
+                "ref": "main",
+            })).collect::<Vec<_>>())),
+            _ => MockResponse::json(200, serde_json::json!([])),
+        }).await;
+        let args = args(&["--per-status-limit", "failed=2", "--per-status-limit", "canceled=1"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        let mut erased = summary.erased_ids.clone();
+        erased.sort();
+        assert_eq!(erased, vec![1, 2, 4]);
+        assert!(summary.truncated);
+    }
+
+    #[tokio::test]
+    async fn the_events_of_a_run_are_written_as_ndjson() {
+        std::env::set_var("GITLAB_CLEANER_TEST_TOKEN", "token");
+        let server = erasing_server(1..=2).await;
+        let args = args(&["--output", "ndjson"]);
+        let system = ActorSystem::new("test", EventBus::new(1000));
+        let sink = Sink::default();
+        let subscribers = vec![tokio::spawn(actors::event::write_ndjson(system.events(), sink.clone()))];
+        let git = Git::new(GitConfig {
+            base_url: server.url.clone(),
+            token_env: Some("GITLAB_CLEANER_TEST_TOKEN".to_string()),
+            ..GitConfig::default()
+        }).unwrap();
+        let git_ref = GitPool::create(&system, "git-actor", git, args.git_actors()).await.unwrap();
+        let displ_ref = system.create_actor("displ-actor", Displ::new(DisplConfig { buffered: true, ..DisplConfig::default() })).await.unwrap();
+        clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        shutdown(system, git_ref, displ_ref, subscribers).await;
+
+        let events: Vec<serde_json::Value> = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap().lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let mut names: Vec<_> = events.iter().map(|event| event["event"].as_str().unwrap().to_string()).collect();
+        names.sort();
+        assert_eq!(names, vec!["erased", "erased", "page_fetched"]);
+        assert!(events.iter().all(|event| event["project_id"] == 1 && event["target"] == "jobs"));
+    }
+
+    #[tokio::test]
+    async fn the_cutoff_is_computed_from_the_pretended_date() {
+        let server = MockServer::start(|request| match (request.method.as_str(), request.query("page")) {
+            ("POST", _) => MockResponse::json(201, job_json(1, "2024-01-01T00:00:00Z")),
+            (_, Some("1")) => MockResponse::json(200, serde_json::json!([job_json(2, "2024-01-30T00:00:00Z"), job_json(1, "2024-01-01T00:00:00Z")])),
+            (_, Some(_)) => MockResponse::json(200, serde_json::json!([])),
+            _ => MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project" })),
+        }).await;
+        let args = Args::try_parse_from(["gitlab-cleaner", "--gitlab-url", "https://host", "--project-id", "1", "--skip-version-check",
+            "--pretend-now", "2024-02-01T00:00:00Z", "10"]).unwrap();
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        assert_eq!(run(&git_ref, &displ_ref, &args, &*args.clock(args.base_clock())).await, 0);
+        assert_eq!(server.paths("POST"), vec!["/api/v4/projects/1/jobs/1/erase"]);
+    }
+
+    #[tokio::test]
+    async fn only_the_failed_runs_are_reported() {
+        for (erase_status, exit_code) in [(201, 0), (403, 1)] {
+            let server = MockServer::start(move |request| match (request.method.as_str(), request.query("page")) {
+                ("POST", _) => MockResponse::json(erase_status, job_json(1, "2024-01-01T00:00:00Z")),
+                (_, Some("1")) => MockResponse::json(200, serde_json::json!([job_json(1, "2024-01-01T00:00:00Z")])),
+                (_, Some(_)) => MockResponse::json(200, serde_json::json!([])),
+                _ => MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project" })),
+            }).await;
+            let args = Args::try_parse_from(["gitlab-cleaner", "--gitlab-url", "https://host", "--project-id", "1", "--skip-version-check",
+                "--report-only-failures"]).unwrap();
+            let (git_ref, _) = actors(&server, &args, GitConfig::default()).await;
+            let printed = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let displ = Displ::new(DisplConfig { colors: false, buffered: true, captured: Some(printed.clone()), ..DisplConfig::default() });
+            let displ_ref = ActorSystem::new("test", EventBus::new(1000)).create_actor("displ-actor", displ).await.unwrap();
+            assert_eq!(run(&git_ref, &displ_ref, &args, &SystemClock).await, exit_code);
+            let printed = printed.lock().unwrap();
+            assert_eq!(printed.is_empty(), exit_code == 0, "{:?}", printed);
+            assert!(exit_code == 0 || printed.iter().any(|line| line.contains("403")), "{:?}", printed);
+        }
+    }
+
+    #[test]
+    fn dry_run_lines_are_sorted_from_the_oldest() {
+        let jobs = [job(2, "2024-01-02T00:00:00Z", "failed", "main"), job(1, "2024-01-01T00:00:00Z", "success", "main")];
+        assert_eq!(dry_run_lines("jobs", &jobs, None), vec![
+            "[dry-run] jobs 1 created at 2024-01-01T00:00:00+00:00 would be erased.",
+            "[dry-run] jobs 2 created at 2024-01-02T00:00:00+00:00 would be erased.",
+        ]);
+    }
+
+    #[test]
+    fn dry_run_lines_are_grouped_with_a_sample() {
+        let mut jobs: Vec<Job> = (1..=6).map(|id| job(id, &format!("2024-01-0{}T00:00:00Z", id), "failed", "main")).collect();
+        jobs.push(job(7, "2024-02-01T00:00:00Z", "failed", "dev"));
+        assert_eq!(dry_run_lines("jobs", &jobs, Some(DryRunGroup::Ref)), vec![
+            "[dry-run] dev: 1 jobs created from 2024-02-01T00:00:00+00:00 to 2024-02-01T00:00:00+00:00 would be erased, e.g. 7.",
+            "[dry-run] main: 6 jobs created from 2024-01-01T00:00:00+00:00 to 2024-01-06T00:00:00+00:00 would be erased, e.g. 1, 2, 3, 4, 5, ...",
+        ]);
+    }
+
+    #[test]
+    fn parse_selection_accepts_the_numbers_in_range() {
+        assert_eq!(parse_selection(" 2\n", 3), Some(1));
+        assert_eq!(parse_selection("1", 1), Some(0));
+        assert_eq!(parse_selection("0", 3), None);
+        assert_eq!(parse_selection("4", 3), None);
+        assert_eq!(parse_selection("first", 3), None);
+    }
+
+    #[tokio::test]
+    async fn only_the_jobs_of_the_id_window_are_erased() {
+        let server = erasing_server(1..=6).await;
+        let args = args(&["--min-job-id", "2", "--max-job-id", "4"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        let mut erased = summary.erased_ids.clone();
+        erased.sort();
+        assert_eq!(erased, vec![2, 3, 4]);
+
+        let now = parse_date("2024-06-01T00:00:00Z").unwrap();
+        assert!(!job_matches(&job(5, "2024-01-01T00:00:00Z", "failed", "main"), &args, now));
+        assert!(job_matches(&job(4, "2024-01-01T00:00:00Z", "failed", "main"), &args, now));
+    }
+
+    #[tokio::test]
+    async fn ordered_erases_complete_one_at_a_time_by_id() {
+        let server = MockServer::start(|request| match (request.method.as_str(), request.query("page")) {
+            // The first jobs answer last, so that concurrent erases would complete out of order.
+            ("POST", _) => {
+                let id: u64 = request.path.split('/').nth(6).unwrap().parse().unwrap();
+                MockResponse::json(201, job_json(id, "2024-01-01T00:00:00Z")).delayed(Duration::from_millis(60 - id * 10))
+            }
+            (_, Some("1")) => MockResponse::json(200, serde_json::json!([3, 5, 1, 4, 2].map(|id| job_json(id, "2024-01-01T00:00:00Z")))),
+            _ => MockResponse::json(200, serde_json::json!([])),
+        }).await;
+        let args = args(&["--ordered"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        assert_eq!(summary.erased_ids, vec![1, 2, 3, 4, 5]);
+        // A single erase being in flight at a time, each one completes before the next one is sent.
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 1);
+        assert_eq!(server.paths("POST"), (1..=5).map(|id| format!("/api/v4/projects/1/jobs/{}/erase", id)).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn a_missing_listing_is_skipped_with_ignore_404_on_list() {
+        let server = MockServer::start(|request| match (request.method.as_str(), request.query("page")) {
+            ("POST", _) => MockResponse::json(201, job_json(1, "2024-01-01T00:00:00Z")),
+            _ if request.path.contains("/packages") => MockResponse::json(404, serde_json::json!({ "message": "404 Not Found" })),
+            (_, Some("1")) => MockResponse::json(200, serde_json::json!([job_json(1, "2024-01-01T00:00:00Z")])),
+            _ => MockResponse::json(200, serde_json::json!([])),
+        }).await;
+        let args = args(&["-t", "packages,jobs", "--ignore-404-on-list"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let summaries = clean_project(&git_ref, &displ_ref, 1, &args.target, None, None, &args, &SystemClock).await;
+        assert_eq!(summaries.iter().map(|summary| summary.erased).sum::<u64>(), 1);
+        assert!(displayed(&displ_ref).await.iter().any(|line| line.contains("skipped the packages")));
+    }
+
+    #[tokio::test]
+    async fn the_date_of_the_server_drives_the_cutoff() {
+        let server = MockServer::start(|request| match (request.method.as_str(), request.query("page")) {
+            ("POST", _) => MockResponse::json(201, job_json(1, "2024-01-01T00:00:00Z")),
+            (_, Some("1")) => MockResponse::json(200, serde_json::json!([job_json(2, "2024-01-30T00:00:00Z"), job_json(1, "2024-01-01T00:00:00Z")])),
+            (_, Some(_)) => MockResponse::json(200, serde_json::json!([])),
+            _ => MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project", "version": "16.5.0", "revision": "abc" }))
+                .header("Date", "Thu, 01 Feb 2024 00:00:00 GMT"),
+        }).await;
+        let mut args = Args::try_parse_from(["gitlab-cleaner", "--gitlab-url", "https://host", "--project-id", "1", "--skip-version-check",
+            "--use-server-time", "10"]).unwrap();
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        // The local clock is a year ahead of the instance.
+        let base_clock: Arc<dyn Clock> = Arc::new(MockClock::new(parse_date("2025-02-01T00:00:00Z").unwrap()));
+        args.clock_skew_secs = server_clock_skew(&git_ref, &displ_ref, &*base_clock).await.unwrap();
+        assert_eq!(args.clock_skew_secs, -366 * 24 * 60 * 60);
+        assert_eq!(run(&git_ref, &displ_ref, &args, &*args.clock(base_clock)).await, 0);
+        assert_eq!(server.paths("POST"), vec!["/api/v4/projects/1/jobs/1/erase"]);
+    }
+
+    #[tokio::test]
+    async fn exactly_the_first_candidates_are_previewed() {
+        let server = erasing_server(1..=5).await;
+        let args = args(&["--preview", "2", "--yes"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        let gate = Gate::new(1);
+        let summary = clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, Some(&gate)).await;
+        assert_eq!(summary.erased, 5);
+        let previewed: Vec<String> = displayed(&displ_ref).await.into_iter().filter(|line| line.starts_with("[preview]")).collect();
+        assert_eq!(previewed, vec![
+            "[preview] jobs 1 created at 2024-01-01T00:00:00+00:00 will be erased.",
+            "[preview] jobs 2 created at 2024-01-01T00:00:00+00:00 will be erased.",
+        ]);
+    }
+
+    #[test]
+    fn format_size_uses_the_largest_unit() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+        assert_eq!(format_size(u64::MAX), "16777216.0 TiB");
+    }
+
+    #[tokio::test]
+    async fn the_dry_run_estimates_the_reclaimed_size() {
+        let server = MockServer::start(|request| match request.query("page") {
+            Some("1") => MockResponse::json(200, serde_json::json!([1024, 512].map(|size| serde_json::json!({
+                "id": size,
+                "created_at": "2024-01-01T00:00:00Z",
+                "status": "failed",
+                "ref": "main",
+                "artifacts": [{ "file_type": "archive", "size": size }, { "file_type": "trace", "size": size }],
+            })))),
+            _ => MockResponse::json(200, serde_json::json!([])),
+        }).await;
+        let args = args(&["--dry-run", "--summary-only"]);
+        let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+        clean_jobs(&git_ref, &displ_ref, 1, None, None, &args, &SystemClock, None).await;
+        assert!(displayed(&displ_ref).await.contains(&"[dry-run] Would reclaim ~3.0 KiB across 2 jobs.".to_string()));
+    }
+
+    #[test]
+    fn job_matches_the_selection() {
+        let args = args(&["--select", "status == \"failed\" && age > 30d"]);
+        let failed = job(1, "2024-01-01T00:00:00Z", "failed", "main");
+        assert!(job_matches(&failed, &args, parse_date("2024-06-01T00:00:00Z").unwrap()));
+        assert!(!job_matches(&failed, &args, parse_date("2024-01-15T00:00:00Z").unwrap()));
+        assert!(!job_matches(&job(2, "2024-01-01T00:00:00Z", "success", "main"), &args, parse_date("2024-06-01T00:00:00Z").unwrap()));
+    }
+
+    #[test]
+    fn csv_field_is_quoted_when_needed() {
+        assert_eq!(csv_field("build"), "build");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn json_export_is_a_valid_array() {
+        let path = temp_file("export.json");
+        start_export(&path).unwrap();
+        finish_export(&path).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<serde_json::Value>>(&std::fs::read_to_string(&path).unwrap()).unwrap(), Vec::<serde_json::Value>::new());
+
+        start_export(&path).unwrap();
+        export_candidates(&path, 7, &[job(1, "2024-01-01T00:00:00Z", "failed", "main")]).unwrap();
+        export_candidates(&path, 8, &[]).unwrap();
+        export_candidates(&path, 9, &[job(2, "2024-01-02T00:00:00Z", "success", "dev")]).unwrap();
+        finish_export(&path).unwrap();
+        let exported: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(exported.iter().map(|job| (job["project_id"].as_u64(), job["id"].as_u64())).collect::<Vec<_>>(),
+            vec![(Some(7), Some(1)), (Some(9), Some(2))]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn csv_export_has_a_header_and_a_line_per_job() {
+        let path = temp_file("export.csv");
+        start_export(&path).unwrap();
+        export_candidates(&path, 7, &[job(1, "2024-01-01T00:00:00Z", "failed", "a,b")]).unwrap();
+        finish_export(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), format!(
+            "{}\n7,1,,failed,\"a,b\",2024-01-01T00:00:00+00:00,,,,,,0\n", EXPORT_CSV_HEADER));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_the_matched_jobs_are_exported() {
+        let server = MockServer::start(|request| match (request.method.as_str(), request.query("page")) {
+            ("POST", _) => MockResponse::json(201, job_json(1, "2024-01-01T00:00:00Z")),
+            (_, Some("1")) => MockResponse::json(200, serde_json::json!([
+                job_json(3, "2024-01-01T00:00:00Z"),
+                { "id": 2, "created_at": "2024-01-01T00:00:00Z", "status": "success", "ref": "main" },
+                job_json(1, "2024-01-01T00:00:00Z"),
+            ])),
+            (_, Some(_)) => MockResponse::json(200, serde_json::json!([])),
+            _ => MockResponse::json(200, serde_json::json!({ "id": 1, "name": "project" })),
+        }).await;
+        let path = temp_file("candidates.json");
+        for extra in [&[][..], &["--dry-run"][..]] {
+            let args = Args::try_parse_from(["gitlab-cleaner", "--gitlab-url", "https://host", "--project-id", "1", "--skip-version-check",
+                "--status", "failed", "--export-candidates", path.to_str().unwrap()].iter().chain(extra)).unwrap();
+            let (git_ref, displ_ref) = actors(&server, &args, GitConfig::default()).await;
+            assert_eq!(run(&git_ref, &displ_ref, &args, &SystemClock).await, 0);
+            let exported: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            assert_eq!(exported.iter().map(|job| job["id"].as_u64().unwrap()).collect::<Vec<_>>(), vec![3, 1], "{:?}", extra);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
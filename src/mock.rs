@@ -0,0 +1,211 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}, time::Duration};
+
+use reqwest::StatusCode;
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}};
+
+/// A request received by the mock Gitlab instance.
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    /// The method of the request, e.g. `GET`.
+    pub method: String,
+    /// The path of the request, with its query.
+    pub path: String,
+    /// The headers of the request, by their lowercase names.
+    pub headers: HashMap<String, String>,
+}
+
+impl MockRequest {
+    /// The value of a parameter of the query, if any.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.path.split_once('?')?.1
+            .split('&')
+            .find_map(|pair| pair.split_once('=').filter(|(key, _)| *key == name).map(|(_, value)| value))
+    }
+
+    /// The value of a header of the request, if any.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+}
+
+/// A response sent by the mock Gitlab instance.
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    /// The status of the response.
+    pub status: u16,
+    /// The headers of the response, on top of the content length.
+    pub headers: Vec<(String, String)>,
+    /// The body of the response, as bytes so that it can be binary.
+    pub body: Vec<u8>,
+    /// How long the response is held back before being sent.
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    /// A response with a JSON body.
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        MockResponse {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.to_string().into_bytes(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// A response with a JSON body encoded with gzip, in stored deflate blocks since only the encoding matters.
+    pub fn gzip_json(status: u16, body: serde_json::Value) -> Self {
+        let data = body.to_string().into_bytes();
+        let mut gzip = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+        let mut chunks = data.chunks(u16::MAX as usize).peekable();
+        if chunks.peek().is_none() {
+            gzip.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+        }
+        while let Some(chunk) = chunks.next() {
+            let length = chunk.len() as u16;
+            gzip.push(chunks.peek().is_none() as u8);
+            gzip.extend_from_slice(&length.to_le_bytes());
+            gzip.extend_from_slice(&(!length).to_le_bytes());
+            gzip.extend_from_slice(chunk);
+        }
+        gzip.extend_from_slice(&crc32(&data).to_le_bytes());
+        gzip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        MockResponse {
+            body: gzip,
+            ..MockResponse::json(status, serde_json::Value::Null).header("Content-Encoding", "gzip")
+        }
+    }
+
+    /// A response without body, e.g. a 204 No Content.
+    pub fn empty(status: u16) -> Self {
+        MockResponse { status, headers: Vec::new(), body: Vec::new(), delay: Duration::ZERO }
+    }
+
+    /// Adds a header to the response.
+    pub fn header(mut self, name: &str, value: impl ToString) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Holds the response back for the given duration.
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// The CRC-32 checksum of the data, as found in the gzip trailer.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Mock Gitlab instance answering each request with the response of a handler, on a local port.
+/// The requests are recorded, along with the highest number of requests handled at once.
+pub struct MockServer {
+    /// The base url of the instance, e.g. `http://127.0.0.1:1234`.
+    pub url: String,
+    /// The requests received so far.
+    pub requests: Arc<Mutex<Vec<MockRequest>>>,
+    /// The highest number of requests handled at once so far.
+    pub max_in_flight: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+    /// Starts the instance, each request being answered by the handler.
+    pub async fn start(handler: impl Fn(&MockRequest) -> MockResponse + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let handler = Arc::new(handler);
+        let (recorded, max) = (requests.clone(), max_in_flight.clone());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (handler, recorded, in_flight, max) = (handler.clone(), recorded.clone(), in_flight.clone(), max.clone());
+                tokio::spawn(async move {
+                    let _ = serve(stream, &*handler, &recorded, &in_flight, &max).await;
+                });
+            }
+        });
+        MockServer { url, requests, max_in_flight }
+    }
+
+    /// The paths of the requests received so far with the given method.
+    pub fn paths(&self, method: &str) -> Vec<String> {
+        self.requests.lock().unwrap().iter()
+            .filter(|request| request.method == method)
+            .map(|request| request.path.clone())
+            .collect()
+    }
+}
+
+/// Answers the requests of a connection until it is closed.
+async fn serve(
+    mut stream: TcpStream,
+    handler: &(dyn Fn(&MockRequest) -> MockResponse + Send + Sync),
+    recorded: &Mutex<Vec<MockRequest>>,
+    in_flight: &AtomicUsize,
+    max_in_flight: &AtomicUsize) -> std::io::Result<()> {
+    let mut buffer: Vec<u8> = Vec::new();
+    loop {
+        let head_end = loop {
+            if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                break end + 4;
+            }
+            let mut chunk = [0; 4096];
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+        };
+        let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default().to_string();
+        let path = request_line.next().unwrap_or_default().to_string();
+        let headers: HashMap<String, String> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        let length = headers.get("content-length").and_then(|length| length.parse::<usize>().ok()).unwrap_or(0);
+        while buffer.len() < head_end + length {
+            let mut chunk = [0; 4096];
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+        }
+        buffer.drain(..head_end + length);
+
+        let request = MockRequest { method, path, headers };
+        recorded.lock().unwrap().push(request.clone());
+        let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        max_in_flight.fetch_max(current, Ordering::SeqCst);
+        let response = handler(&request);
+        tokio::time::sleep(response.delay).await;
+        in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let status = StatusCode::from_u16(response.status).unwrap();
+        let mut reply = format!("HTTP/1.1 {} {}\r\nContent-Length: {}\r\n",
+            status.as_u16(), status.canonical_reason().unwrap_or_default(), response.body.len());
+        for (name, value) in &response.headers {
+            reply.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        reply.push_str("\r\n");
+        let mut reply = reply.into_bytes();
+        // The response to a HEAD request announces the length of its body without sending it.
+        if request.method != "HEAD" {
+            reply.extend_from_slice(&response.body);
+        }
+        stream.write_all(&reply).await?;
+    }
+}
@@ -0,0 +1,342 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{actors::git::Job, cli::parse_interval};
+
+/// Expression selecting the jobs from their fields, e.g. `status == "failed" && age > 30d && ref != "main"`.
+/// The comparisons are combined with `&&`, `||`, `!` and parentheses, `&&` binding tighter than `||`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selection {
+    /// Both expressions hold.
+    And(Box<Selection>, Box<Selection>),
+    /// Either expression holds.
+    Or(Box<Selection>, Box<Selection>),
+    /// The expression does not hold.
+    Not(Box<Selection>),
+    /// A field of the job compared to a value of the same kind.
+    Compare(Field, Operator, Value),
+}
+
+/// The fields of a job available to the expressions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    /// The status of the job, e.g. `failed`.
+    Status,
+    /// The branch or tag the job ran for.
+    Ref,
+    /// The name of the job.
+    Name,
+    /// The status of the pipeline of the job, empty when unknown.
+    PipelineStatus,
+    /// The id of the job.
+    Id,
+    /// The size in bytes of the artifacts of the job.
+    Size,
+    /// The time elapsed since the creation of the job, e.g. `30d`.
+    Age,
+}
+
+/// The comparison operators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+/// The values compared to the fields.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A quoted text, e.g. `"main"`.
+    Text(String),
+    /// A number, e.g. `1024`.
+    Number(u64),
+    /// A duration with its unit, e.g. `12h` or `30d`.
+    Age(Duration),
+}
+
+/// The tokens of an expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Field(String),
+    Value(Value),
+    Operator(Operator),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl Selection {
+    /// Parse an expression, checking that each field is compared to a value of its kind.
+    pub fn parse(expression: &str) -> Result<Selection, String> {
+        let mut parser = Parser { tokens: tokenize(expression)?, position: 0 };
+        let selection = parser.or()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(selection),
+            Some(token) => Err(format!("Unexpected {:?} in the selection '{}'.", token, expression)),
+        }
+    }
+
+    /// Whether the job is selected by the expression, its age being computed from now.
+    pub fn matches(&self, job: &Job, now: DateTime<Utc>) -> bool {
+        match self {
+            Selection::And(left, right) => left.matches(job, now) && right.matches(job, now),
+            Selection::Or(left, right) => left.matches(job, now) || right.matches(job, now),
+            Selection::Not(selection) => !selection.matches(job, now),
+            Selection::Compare(field, operator, value) => {
+                let ordering = match (field, value) {
+                    (Field::Status, Value::Text(text)) => job.status.as_str().cmp(text),
+                    (Field::Ref, Value::Text(text)) => job.git_ref.as_str().cmp(text),
+                    (Field::Name, Value::Text(text)) => job.name.as_str().cmp(text),
+                    (Field::PipelineStatus, Value::Text(text)) =>
+                        job.pipeline.as_ref().map_or("", |pipeline| pipeline.status.as_str()).cmp(text),
+                    (Field::Id, Value::Number(number)) => job.id.cmp(number),
+                    (Field::Size, Value::Number(number)) => job.artifacts_size().cmp(number),
+                    (Field::Age, Value::Age(age)) => (now - job.created_at).cmp(age),
+                    // The kinds are checked when parsing.
+                    _ => return false,
+                };
+                operator.holds(ordering)
+            }
+        }
+    }
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Field, String> {
+        match name {
+            "status" => Ok(Field::Status),
+            "ref" => Ok(Field::Ref),
+            "name" => Ok(Field::Name),
+            "pipeline_status" => Ok(Field::PipelineStatus),
+            "id" => Ok(Field::Id),
+            "size" => Ok(Field::Size),
+            "age" => Ok(Field::Age),
+            _ => Err(format!("Unknown field '{}', expected status, ref, name, pipeline_status, id, size or age.", name)),
+        }
+    }
+
+    /// Whether the value is of the kind of the field, and can be compared with the operator.
+    fn accepts(&self, operator: Operator, value: &Value) -> bool {
+        match (self, value) {
+            (Field::Status | Field::Ref | Field::Name | Field::PipelineStatus, Value::Text(_)) =>
+                operator == Operator::Eq || operator == Operator::Ne,
+            (Field::Id | Field::Size, Value::Number(_)) | (Field::Age, Value::Age(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Operator {
+    /// Whether the operator holds for the ordering of the field relative to the value.
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Eq => ordering == Ordering::Equal,
+            Operator::Ne => ordering != Ordering::Equal,
+            Operator::Lt => ordering == Ordering::Less,
+            Operator::Le => ordering != Ordering::Greater,
+            Operator::Gt => ordering == Ordering::Greater,
+            Operator::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// Split an expression into tokens.
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            ' ' | '\t' => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Eq),
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Ne),
+            '!' => Token::Not,
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Le),
+            '<' => Token::Operator(Operator::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Ge),
+            '>' => Token::Operator(Operator::Gt),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err(format!("Unterminated text in the selection '{}'.", expression)),
+                    }
+                }
+                Token::Value(Value::Text(text))
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    word.push(c);
+                }
+                if !c.is_ascii_digit() {
+                    Token::Field(word)
+                } else if let Ok(number) = word.parse() {
+                    Token::Value(Value::Number(number))
+                } else {
+                    let age = parse_interval(&word)?;
+                    Token::Value(Value::Age(Duration::from_std(age).map_err(|err| err.to_string())?))
+                }
+            }
+            c => return Err(format!("Unexpected '{}' in the selection '{}'.", c, expression)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser of the tokens of an expression.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn next_if(&mut self, expected: Token) -> bool {
+        let matched = self.tokens.get(self.position) == Some(&expected);
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Result<Selection, String> {
+        let mut selection = self.and()?;
+        while self.next_if(Token::Or) {
+            selection = Selection::Or(Box::new(selection), Box::new(self.and()?));
+        }
+        Ok(selection)
+    }
+
+    fn and(&mut self) -> Result<Selection, String> {
+        let mut selection = self.unary()?;
+        while self.next_if(Token::And) {
+            selection = Selection::And(Box::new(selection), Box::new(self.unary()?));
+        }
+        Ok(selection)
+    }
+
+    fn unary(&mut self) -> Result<Selection, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Selection::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let selection = self.or()?;
+                if !self.next_if(Token::Close) {
+                    return Err("Missing ')' in the selection.".to_string());
+                }
+                Ok(selection)
+            }
+            Some(Token::Field(name)) => {
+                let field = Field::parse(&name)?;
+                match (self.next(), self.next()) {
+                    (Some(Token::Operator(operator)), Some(Token::Value(value))) if field.accepts(operator, &value) =>
+                        Ok(Selection::Compare(field, operator, value)),
+                    (Some(Token::Operator(_)), Some(Token::Value(value))) =>
+                        Err(format!("The field '{}' cannot be compared this way to {:?}.", name, value)),
+                    _ => Err(format!("Expected a comparison after the field '{}', e.g. {} == \"failed\" or age > 30d.", name, name)),
+                }
+            }
+            token => Err(format!("Expected a comparison, found {:?}.", token)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(status: &str, git_ref: &str, id: u64, size: u64, created_at: &str) -> Job {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "created_at": created_at,
+            "status": status,
+            "ref": git_ref,
+            "name": "build",
+            "artifacts": [{ "file_type": "archive", "size": size }],
+            "pipeline": { "id": 1, "status": "success" },
+        })).unwrap()
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn matches(expression: &str, job: &Job) -> bool {
+        Selection::parse(expression).unwrap().matches(job, now())
+    }
+
+    #[test]
+    fn parse_binds_and_tighter_than_or() {
+        let compare = |field, value: &str| Box::new(Selection::Compare(field, Operator::Eq, Value::Text(value.to_string())));
+        assert_eq!(Selection::parse("status == \"failed\" || ref == \"main\" && name == \"build\""), Ok(Selection::Or(
+            compare(Field::Status, "failed"),
+            Box::new(Selection::And(compare(Field::Ref, "main"), compare(Field::Name, "build"))),
+        )));
+        assert_eq!(Selection::parse("!(id >= 10)"), Ok(Selection::Not(Box::new(Selection::Compare(Field::Id, Operator::Ge, Value::Number(10))))));
+        assert_eq!(Selection::parse("age > 12h"), Ok(Selection::Compare(Field::Age, Operator::Gt, Value::Age(Duration::hours(12)))));
+    }
+
+    #[test]
+    fn parse_rejects_the_invalid_expressions() {
+        for expression in [
+            "",
+            "status",
+            "status == failed",
+            "status > \"failed\"",
+            "age > 10",
+            "size == \"big\"",
+            "owner == \"me\"",
+            "(status == \"failed\"",
+            "status == \"failed",
+            "status == \"failed\" status == \"failed\"",
+            "age > 3w",
+            "age > 99999999999999999d",
+            "id == 1 # comment",
+        ] {
+            assert!(Selection::parse(expression).is_err(), "{} should be rejected", expression);
+        }
+    }
+
+    #[test]
+    fn matches_the_fields_of_the_job() {
+        let failed = job("failed", "feature", 42, 2048, "2024-04-01T00:00:00Z");
+        assert!(matches("status == \"failed\" && age > 30d && ref != \"main\"", &failed));
+        assert!(!matches("status == \"failed\" && age > 90d", &failed));
+        assert!(matches("pipeline_status == \"success\" && name == \"build\"", &failed));
+        assert!(matches("size >= 2048 && size < 4096 && id == 42", &failed));
+        assert!(matches("id < 10 || !(ref == \"main\")", &failed));
+        assert!(!matches("!(status != \"success\")", &failed));
+    }
+
+    #[test]
+    fn matches_an_empty_pipeline_status_when_unknown() {
+        let mut job = job("failed", "main", 1, 0, "2024-05-01T00:00:00Z");
+        job.pipeline = None;
+        assert!(matches("pipeline_status == \"\"", &job));
+        assert!(matches("size == 0", &job));
+    }
+}